@@ -1,103 +1,93 @@
+use crate::config::Config;
 use crate::puzzle::Puzzle;
-use npuzzle::{neighbors, HeuristicFn, Mode, NodeWithCost, Solution};
-use std::collections::{BinaryHeap, HashMap};
+use npuzzle::{blank_moves, neighbors, Board, Heuristic, NodeWithCost, Solution};
+use std::collections::{BinaryHeap, HashMap, HashSet};
 use std::time::Instant;
 
-fn reconstruct_path(paths: &HashMap<Vec<i32>, Vec<i32>>, node: &[i32]) -> Vec<Vec<i32>> {
-    let mut full_path = vec![Vec::from(node)];
+fn reconstruct_path(paths: &HashMap<Board, Board>, node: &Board) -> Vec<Vec<i32>> {
+    let mut full_path = vec![node.to_vec()];
 
     let mut current = node;
     while paths.contains_key(current) {
         current = &paths[current];
-        full_path.push(Vec::from(current));
+        full_path.push(current.to_vec());
     }
 
     full_path.reverse();
     full_path
 }
 
-pub fn solve(puzzle: &Puzzle, mode: &str, heuristic: HeuristicFn) -> Result<Solution, String> {
+pub fn solve(puzzle: &Puzzle, config: &Config, heuristic: Heuristic) -> Result<Solution, String> {
     let now = Instant::now();
-    let mode = match mode {
-        "greedy" => Mode::Greedy,
-        "uniform" => Mode::Uniform,
-        _ => Mode::Normal,
-    };
+    let mode = config.mode();
+    let map = Board::from(&puzzle.map[..]);
+    let goal = Board::from(&puzzle.goal[..]);
 
     // Summary
     let mut total_used_states = 0;
     let mut biggest_state: usize = 1; // 1 is the initial state
 
     // State
+    let h = heuristic.evaluate(puzzle.size, &map, &goal);
     let mut open_set = BinaryHeap::new();
     open_set.push(NodeWithCost {
-        cost: 0.,
-        node: puzzle.map.clone(),
+        cost: mode.cost(0., h),
+        node: map.clone(),
     });
-    let mut open_set_ref: HashMap<Vec<i32>, bool> = HashMap::new();
-    open_set_ref.insert(puzzle.map.clone(), true);
-    // Keep a reference of already visited nodes to ignore
-    // -- since heuristics needs to be admissibles revisiting a node is unnecessary
-    let mut closed: HashMap<Vec<i32>, bool> = HashMap::new();
+    // Nodes already expanded with their best known cost
+    let mut closed: HashSet<Board> = HashSet::new();
     // cameFrom -- best previous path to a node
-    let mut best_path_to_node: HashMap<Vec<i32>, Vec<i32>> = HashMap::new();
-    // gScore -- cost of the best path to a node
-    let mut best_cost_to_node: HashMap<Vec<i32>, f64> = HashMap::new();
-    best_cost_to_node.insert(puzzle.map.clone(), 0.);
+    let mut best_path_to_node: HashMap<Board, Board> = HashMap::new();
+    // gScore -- cost of the best path to a node, and h(n) to evaluate its neighbors incrementally
+    let mut best_cost_to_node: HashMap<Board, (f64, f64)> = HashMap::new();
+    best_cost_to_node.insert(map.clone(), (0., h));
 
     // Iterate on each cells
     while let Some(current) = open_set.pop() {
-        open_set_ref.remove_entry(&current.node);
-        closed.insert(current.node.clone(), true);
+        let (current_cost, current_h) = best_cost_to_node[&current.node];
+        // Ignore outdated entries of nodes that were found again with a better cost
+        if closed.contains(&current.node) || current.cost != mode.cost(current_cost, current_h) {
+            continue;
+        }
+        closed.insert(current.node.clone());
         total_used_states += 1;
 
         // Check if it's the goal
-        if current.node == puzzle.goal {
+        if current.node == goal {
+            let steps = reconstruct_path(&best_path_to_node, &current.node);
             return Ok(Solution {
                 total_used_states,
                 biggest_state,
-                steps: reconstruct_path(&best_path_to_node, &current.node),
+                moves: blank_moves(puzzle.size, &steps)?,
+                steps,
+                expanded_by_direction: None,
+                suboptimality_bound: mode.suboptimality_bound(&heuristic),
+                transposition_hits: None,
+                layer_histogram: None,
             });
         }
 
+        let next_move_cost = current_cost + 1.;
         for neighbor in neighbors(puzzle.size, &current.node).into_iter().flatten() {
-            // Ignore nodes already in closed set
-            if closed.contains_key(&neighbor) {
-                continue;
-            }
-
-            let next_move_cost = best_cost_to_node.get(&current.node).unwrap() + 1.;
             let neighbor_previous_cost = best_cost_to_node.get(&neighbor);
             // Check the node only if it was never checked or if it has a better cost than the last found
-            if neighbor_previous_cost.is_none()
-                || next_move_cost < *(neighbor_previous_cost.unwrap())
+            if neighbor_previous_cost.is_some()
+                && next_move_cost >= neighbor_previous_cost.unwrap().0
             {
-                // Update each state hash maps
-                if !best_path_to_node.contains_key(&neighbor) {
-                    best_path_to_node.insert(neighbor.clone(), current.node.clone());
-                } else if best_path_to_node[&neighbor] != current.node {
-                    *best_path_to_node.get_mut(&neighbor).unwrap() = current.node.clone();
-                }
-                if !best_cost_to_node.contains_key(&neighbor) {
-                    best_cost_to_node.insert(neighbor.clone(), next_move_cost);
-                } else {
-                    *best_cost_to_node.get_mut(&neighbor).unwrap() = next_move_cost;
-                }
-                // Add to open_set if it's not already inside
-                if !open_set_ref.contains_key(&neighbor) {
-                    open_set_ref.insert(neighbor.clone(), true);
-                    open_set.push(NodeWithCost {
-                        cost: match mode {
-                            Mode::Normal => {
-                                next_move_cost + heuristic(puzzle.size, &neighbor, &puzzle.goal)
-                            }
-                            Mode::Greedy => heuristic(puzzle.size, &neighbor, &puzzle.goal), // Ignore depth
-                            Mode::Uniform => next_move_cost, // Ignore heuristic
-                        },
-                        node: neighbor,
-                    });
-                }
+                continue;
             }
+            let h = match neighbor_previous_cost {
+                Some((_, h)) => *h,
+                None => heuristic.update(puzzle.size, current_h, &current.node, &neighbor, &goal),
+            };
+            best_cost_to_node.insert(neighbor.clone(), (next_move_cost, h));
+            best_path_to_node.insert(neighbor.clone(), current.node.clone());
+            // A node found again with a better cost is opened again, even if it was already expanded
+            closed.remove(&neighbor);
+            open_set.push(NodeWithCost {
+                cost: mode.cost(next_move_cost, h),
+                node: neighbor,
+            });
         }
 
         if open_set.len() > biggest_state {
@@ -105,13 +95,78 @@ pub fn solve(puzzle: &Puzzle, mode: &str, heuristic: HeuristicFn) -> Result<Solu
         }
 
         if total_used_states % 100000 == 0 {
-            println!(
+            config.progress(&format!(
                 "#> Explored {} states in {:.2?}",
                 total_used_states,
                 now.elapsed()
-            );
+            ));
         }
     }
 
     Err(String::from("Failed to find a solution for this puzzle"))
 }
+
+#[test]
+fn weighted_is_bounded() {
+    let config = Config {
+        mode: "weighted".to_string(),
+        weight: Some(2.),
+        ..Config::default()
+    };
+    crate::puzzle::check_solutions(2, |puzzle| {
+        let solution = solve(puzzle, &config, crate::heuristic::MANHATTAN).unwrap();
+        assert_eq!(solution.suboptimality_bound, Some(2.));
+        solution
+    });
+}
+
+#[test]
+fn rectangular_puzzles() {
+    for (path, optimal) in [
+        ("puzzles/solv_2x4_1.map", 22),
+        ("puzzles/solv_3x5_1.map", 30),
+    ] {
+        let puzzle = Puzzle::new(path, "snail").unwrap();
+        let solution = solve(&puzzle, &Config::default(), crate::heuristic::MANHATTAN).unwrap();
+        assert_eq!(solution.steps.len() - 1, optimal, "{}", path);
+        assert_eq!(*solution.steps.last().unwrap(), puzzle.goal);
+        // Each tile slides into the empty cell of the previous step
+        let tiles = npuzzle::blank_to_tile_moves(puzzle.size, &puzzle.map, &solution.moves);
+        for (pair, tile) in solution.steps.windows(2).zip(tiles.unwrap()) {
+            assert_eq!(
+                pair[1][pair[0].iter().position(|&cell| cell == 0).unwrap()],
+                tile
+            );
+        }
+    }
+}
+
+#[test]
+fn custom_goal() {
+    use rand::rngs::StdRng;
+    use rand::SeedableRng;
+    let (size, _, goal) = crate::goal::load("puzzles/goal_3_1.map").unwrap();
+    let table = crate::lookup_table::get(&Board::from(&goal[..]));
+    let mut rng = StdRng::seed_from_u64(20);
+    for _ in 0..20 {
+        let puzzle = Puzzle::generate_for_goal(true, size, goal.clone(), &mut rng);
+        assert!(puzzle.is_solvable());
+        let solution = solve(&puzzle, &Config::default(), crate::heuristic::MANHATTAN).unwrap();
+        let optimal = table.distance(&Board::from(&puzzle.map[..]));
+        assert_eq!(Some(solution.steps.len() as u8 - 1), optimal);
+        assert_eq!(*solution.steps.last().unwrap(), goal);
+    }
+}
+
+#[test]
+fn overestimating_heuristic_has_no_bound() {
+    fn twice_manhattan(size: i32, node: &Board, goal: &Board) -> f64 {
+        2. * crate::heuristic::manhattan(size, node, goal)
+    }
+    let puzzle = Puzzle::new("puzzles/solv_3_1.map", "snail").unwrap();
+    let heuristic = Heuristic::new(twice_manhattan).inadmissible();
+    let solution = solve(&puzzle, &Config::default(), heuristic).unwrap();
+    assert_eq!(solution.suboptimality_bound, None);
+    let solution = solve(&puzzle, &Config::default(), crate::heuristic::MANHATTAN).unwrap();
+    assert_eq!(solution.suboptimality_bound, Some(1.));
+}
@@ -0,0 +1,254 @@
+use crate::config::Config;
+use crate::puzzle::Puzzle;
+use npuzzle::{blank_moves, neighbors, Board, Heuristic, NodeWithCost, Solution};
+use std::collections::{BinaryHeap, HashMap, HashSet};
+use std::time::{Duration, Instant};
+
+// Weight of the first search if no --weight is given
+const INITIAL_WEIGHT: f64 = 3.;
+// Amount removed from the weight after each solution
+const WEIGHT_STEP: f64 = 0.5;
+
+struct State {
+    // gScore and h(n), the heuristic is kept to update the open set when the weight changes
+    best_cost_to_node: HashMap<Board, (f64, f64)>,
+    best_path_to_node: HashMap<Board, Board>,
+    open_set: BinaryHeap<NodeWithCost>,
+    open_set_ref: HashSet<Board>,
+    closed: HashSet<Board>,
+    // Nodes improved after being closed, only expanded again with the next weight
+    inconsistent: HashSet<Board>,
+}
+
+impl State {
+    fn cost(&self, node: &Board, weight: f64) -> f64 {
+        let (g, h) = self.best_cost_to_node[node];
+        g + weight * h
+    }
+
+    fn goal_cost(&self, goal: &Board) -> f64 {
+        self.best_cost_to_node
+            .get(goal)
+            .map(|(g, _)| *g)
+            .unwrap_or(f64::INFINITY)
+    }
+
+    fn push(&mut self, node: Board, weight: f64) {
+        self.open_set.push(NodeWithCost {
+            cost: self.cost(&node, weight),
+            node: node.clone(),
+        });
+        self.open_set_ref.insert(node);
+    }
+
+    fn reconstruct_path(&self, node: &Board) -> Vec<Vec<i32>> {
+        let mut full_path = vec![node.to_vec()];
+        let mut current = node;
+        while let Some(previous) = self.best_path_to_node.get(current) {
+            full_path.push(previous.to_vec());
+            current = previous;
+        }
+        full_path.reverse();
+        full_path
+    }
+}
+
+// Expand nodes until no node in the open set can lead to a better solution with this weight,
+// -- returns false if the time limit was reached before
+fn improve_path(
+    puzzle: &Puzzle,
+    goal: &Board,
+    heuristic: Heuristic,
+    weight: f64,
+    state: &mut State,
+    summary: &mut Solution,
+    deadline: Option<Instant>,
+) -> bool {
+    while let Some(current) = state.open_set.peek() {
+        // h(n) is 0 for the goal
+        if current.cost >= state.goal_cost(goal) {
+            break;
+        }
+        let current = state.open_set.pop().unwrap();
+        // Ignore outdated entries of nodes that were found again with a better cost
+        if state.closed.contains(&current.node) || current.cost != state.cost(&current.node, weight)
+        {
+            continue;
+        }
+        state.open_set_ref.remove(&current.node);
+        state.closed.insert(current.node.clone());
+        summary.total_used_states += 1;
+
+        let (current_cost, current_h) = state.best_cost_to_node[&current.node];
+        let next_move_cost = current_cost + 1.;
+        for neighbor in neighbors(puzzle.size, &current.node).into_iter().flatten() {
+            let neighbor_previous_cost = state.best_cost_to_node.get(&neighbor);
+            if neighbor_previous_cost.is_some()
+                && next_move_cost >= neighbor_previous_cost.unwrap().0
+            {
+                continue;
+            }
+            let h = match neighbor_previous_cost {
+                Some((_, h)) => *h,
+                None => heuristic.update(puzzle.size, current_h, &current.node, &neighbor, goal),
+            };
+            state
+                .best_cost_to_node
+                .insert(neighbor.clone(), (next_move_cost, h));
+            state
+                .best_path_to_node
+                .insert(neighbor.clone(), current.node.clone());
+            if state.closed.contains(&neighbor) {
+                state.inconsistent.insert(neighbor);
+            } else {
+                state.push(neighbor, weight);
+            }
+        }
+
+        if state.open_set.len() > summary.biggest_state {
+            summary.biggest_state = state.open_set.len();
+        }
+        if summary.total_used_states.is_multiple_of(1000)
+            && deadline.is_some_and(|deadline| Instant::now() >= deadline)
+        {
+            return false;
+        }
+    }
+    true
+}
+
+pub fn solve(puzzle: &Puzzle, config: &Config, heuristic: Heuristic) -> Result<Solution, String> {
+    let now = Instant::now();
+    let deadline = config
+        .time_limit
+        .map(|time_limit| now + Duration::from_secs_f64(time_limit));
+    let mut weight = config.weight.unwrap_or(INITIAL_WEIGHT);
+    let map = Board::from(&puzzle.map[..]);
+    let goal = Board::from(&puzzle.goal[..]);
+
+    // Summary, and the best solution found
+    let mut summary = Solution {
+        total_used_states: 0,
+        biggest_state: 1, // 1 is the initial state
+        steps: vec![],
+        moves: vec![],
+        expanded_by_direction: None,
+        suboptimality_bound: None,
+        transposition_hits: None,
+        layer_histogram: None,
+    };
+
+    // State
+    let mut state = State {
+        best_cost_to_node: HashMap::new(),
+        best_path_to_node: HashMap::new(),
+        open_set: BinaryHeap::new(),
+        open_set_ref: HashSet::new(),
+        closed: HashSet::new(),
+        inconsistent: HashSet::new(),
+    };
+    let h = heuristic.evaluate(puzzle.size, &map, &goal);
+    state.best_cost_to_node.insert(map.clone(), (0., h));
+    state.push(map, weight);
+
+    // Bound of the last solution, proven by the weight of the last completed search
+    let mut bound = f64::INFINITY;
+    loop {
+        let completed = improve_path(
+            puzzle,
+            &goal,
+            heuristic,
+            weight,
+            &mut state,
+            &mut summary,
+            deadline,
+        );
+
+        // Publish the solution if it was improved, even if the time limit interrupted the search
+        let goal_cost = state.goal_cost(&goal);
+        if goal_cost == f64::INFINITY {
+            break;
+        }
+        // The optimal solution can't be cheaper than the best g(n) + h(n) left to expand,
+        // -- the weight only bounds the solution once the search with it is completed
+        let lower_bound = state
+            .open_set_ref
+            .iter()
+            .chain(state.inconsistent.iter())
+            .map(|node| state.cost(node, 1.))
+            .fold(goal_cost, f64::min);
+        bound = (goal_cost / lower_bound).min(if completed { weight } else { bound });
+        if summary.steps.len() != (goal_cost as usize) + 1 {
+            summary.steps = state.reconstruct_path(&goal);
+            summary.moves = blank_moves(puzzle.size, &summary.steps)?;
+            config.progress(&format!(
+                "#> Solution found with weight {} ({} steps) in {:.2?}, at most {:.3} times longer than the optimal solution",
+                weight,
+                goal_cost,
+                now.elapsed(),
+                bound
+            ));
+        }
+        summary.suboptimality_bound = heuristic.is_admissible().then_some(bound);
+        if !completed || bound <= 1. {
+            break;
+        }
+
+        // Search again with a lower weight, keeping the previous work
+        weight = (weight - WEIGHT_STEP).max(1.);
+        let open_set: Vec<Board> = state
+            .open_set_ref
+            .drain()
+            .chain(state.inconsistent.drain())
+            .collect();
+        state.open_set.clear();
+        state.closed.clear();
+        for node in open_set {
+            state.push(node, weight);
+        }
+    }
+
+    if summary.steps.is_empty() {
+        if deadline.is_some_and(|deadline| Instant::now() >= deadline) {
+            return Err(String::from("No solution found before the time limit"));
+        }
+        return Err(String::from("Failed to find a solution for this puzzle"));
+    }
+    Ok(summary)
+}
+
+#[test]
+fn reaches_optimal() {
+    crate::puzzle::check_solutions(1, |puzzle| {
+        let solution = solve(puzzle, &Config::default(), crate::heuristic::MANHATTAN).unwrap();
+        assert_eq!(solution.suboptimality_bound, Some(1.));
+        solution
+    });
+}
+
+#[test]
+fn solution_found_before_time_limit() {
+    // Without a heuristic the search goes on after reaching the goal at the 819th expansion,
+    // -- until the time limit is checked at the 1000th one
+    fn zero(_: i32, _: &Board, _: &Board) -> f64 {
+        0.
+    }
+    let config = Config {
+        time_limit: Some(0.),
+        weight: Some(1.),
+        ..Config::default()
+    };
+    let puzzle = Puzzle {
+        size: 4,
+        height: 4,
+        map: vec![2, 13, 3, 4, 1, 14, 15, 5, 12, 9, 11, 6, 10, 0, 8, 7],
+        goal: vec![1, 2, 3, 4, 12, 13, 14, 5, 11, 0, 15, 6, 10, 9, 8, 7],
+    };
+    let solution = solve(&puzzle, &config, Heuristic::new(zero)).unwrap();
+    assert_eq!(solution.steps.len() - 1, 9);
+    assert_eq!(solution.steps[0], puzzle.map);
+    assert_eq!(*solution.steps.last().unwrap(), puzzle.goal);
+    assert!(solution
+        .suboptimality_bound
+        .is_some_and(|bound| bound >= 1.));
+}
@@ -0,0 +1,296 @@
+use npuzzle::Solution;
+use std::fs;
+use std::path::Path;
+use std::time::Duration;
+
+// Runs of each puzzle when no --runs is given
+pub const DEFAULT_RUNS: usize = 3;
+
+pub struct Sample {
+    pub seconds: f64,
+    // States selected by the search
+    pub expanded: usize,
+    // Maximum number of states in memory at once, not a size in bytes
+    pub peak_states: usize,
+    // Highest resident memory added by the search in bytes, if it can be measured
+    pub peak_memory: Option<usize>,
+    pub length: usize,
+}
+
+impl Sample {
+    pub fn new(solution: &Solution, elapsed: Duration, peak_memory: Option<usize>) -> Sample {
+        Sample {
+            seconds: elapsed.as_secs_f64(),
+            expanded: solution.total_used_states,
+            peak_states: solution.biggest_state,
+            peak_memory,
+            length: solution.moves.len(),
+        }
+    }
+}
+
+// Resident memory of the process in bytes, now and at its highest since the last reset,
+// -- only known on Linux
+fn resident_memory() -> Option<(usize, usize)> {
+    let status = fs::read_to_string("/proc/self/status").ok()?;
+    let field = |name: &str| -> Option<usize> {
+        let value = status.lines().find_map(|line| line.strip_prefix(name))?;
+        let kilobytes: usize = value.trim().strip_suffix("kB")?.trim().parse().ok()?;
+        Some(kilobytes * 1024)
+    };
+    Some((field("VmRSS:")?, field("VmHWM:")?))
+}
+
+// Reset the highest resident memory of the process to the current one and return it
+pub fn start_peak_memory() -> Option<usize> {
+    fs::write("/proc/self/clear_refs", "5").ok()?;
+    resident_memory().map(|(current, _)| current)
+}
+
+// Memory added to the process at its highest since start_peak_memory,
+// -- memory freed by the previous runs and reused by the allocator isn't counted
+pub fn peak_memory(start: Option<usize>) -> Option<usize> {
+    let (_, peak) = resident_memory()?;
+    Some(peak.saturating_sub(start?))
+}
+
+// Every run of one variant, heuristic and mode
+pub struct Row {
+    pub variant: String,
+    pub heuristic: String,
+    pub mode: String,
+    pub runs: usize,
+    pub samples: Vec<Sample>,
+    // The last reason a puzzle couldn't be solved with this combination
+    pub error: Option<String>,
+}
+
+impl Row {
+    pub fn new(variant: &str, heuristic: &str, mode: &str) -> Row {
+        Row {
+            variant: variant.to_string(),
+            heuristic: heuristic.to_string(),
+            mode: mode.to_string(),
+            runs: 0,
+            samples: Vec::new(),
+            error: None,
+        }
+    }
+
+    pub fn summary(&self) -> Option<Summary> {
+        let stat = |value: fn(&Sample) -> Option<f64>| {
+            let values: Vec<f64> = self.samples.iter().map(value).collect::<Option<_>>()?;
+            Some(Stat {
+                median: percentile(&values, 0.5)?,
+                p95: percentile(&values, 0.95)?,
+            })
+        };
+        Some(Summary {
+            seconds: stat(|sample| Some(sample.seconds))?,
+            expanded: stat(|sample| Some(sample.expanded as f64))?,
+            peak_states: stat(|sample| Some(sample.peak_states as f64))?,
+            peak_memory: stat(|sample| sample.peak_memory.map(|bytes| bytes as f64)),
+            length: stat(|sample| Some(sample.length as f64))?,
+        })
+    }
+}
+
+pub struct Stat {
+    pub median: f64,
+    pub p95: f64,
+}
+
+pub struct Summary {
+    pub seconds: Stat,
+    pub expanded: Stat,
+    pub peak_states: Stat,
+    // Missing if the memory of a run couldn't be measured
+    pub peak_memory: Option<Stat>,
+    pub length: Stat,
+}
+
+// Bytes in megabytes, or a dash if unknown
+fn megabytes(bytes: Option<f64>) -> String {
+    match bytes {
+        Some(bytes) => format!("{:.1}MB", bytes / (1 << 20) as f64),
+        None => String::from("-"),
+    }
+}
+
+// Linear interpolation between the closest ranks, fraction is between 0 and 1
+pub fn percentile(values: &[f64], fraction: f64) -> Option<f64> {
+    if values.is_empty() {
+        return None;
+    }
+    let mut sorted = values.to_vec();
+    sorted.sort_by(f64::total_cmp);
+    let rank = fraction * (sorted.len() - 1) as f64;
+    let lower = rank.floor() as usize;
+    let upper = rank.ceil() as usize;
+    Some(sorted[lower] + (sorted[upper] - sorted[lower]) * (rank - lower as f64))
+}
+
+pub fn print_table(rows: &[Row]) {
+    println!(
+        "{:<10} {:<17} {:<8} {:>9} {:>10} {:>10} {:>12} {:>12} {:>15} {:>15} {:>11} {:>11} {:>7} {:>7}",
+        "variant",
+        "heuristic",
+        "mode",
+        "solved",
+        "time p50",
+        "time p95",
+        "expanded p50",
+        "expanded p95",
+        "peak states p50",
+        "peak states p95",
+        "memory p50",
+        "memory p95",
+        "len p50",
+        "len p95"
+    );
+    for row in rows {
+        let solved = format!("{}/{}", row.samples.len(), row.runs);
+        match row.summary() {
+            Some(summary) => println!(
+                "{:<10} {:<17} {:<8} {:>9} {:>10.2?} {:>10.2?} {:>12.0} {:>12.0} {:>15.0} {:>15.0} {:>11} {:>11} {:>7.1} {:>7.1}",
+                row.variant,
+                row.heuristic,
+                row.mode,
+                solved,
+                Duration::from_secs_f64(summary.seconds.median),
+                Duration::from_secs_f64(summary.seconds.p95),
+                summary.expanded.median,
+                summary.expanded.p95,
+                summary.peak_states.median,
+                summary.peak_states.p95,
+                megabytes(summary.peak_memory.as_ref().map(|stat| stat.median)),
+                megabytes(summary.peak_memory.as_ref().map(|stat| stat.p95)),
+                summary.length.median,
+                summary.length.p95
+            ),
+            None => println!(
+                "{:<10} {:<17} {:<8} {:>9} {}",
+                row.variant,
+                row.heuristic,
+                row.mode,
+                solved,
+                row.error.as_deref().unwrap_or("No solution found")
+            ),
+        }
+    }
+}
+
+// Quote the fields containing a separator, a quote or a line break
+fn csv_field(value: &str) -> String {
+    if value.contains([',', '"', '\n', '\r']) {
+        format!("\"{}\"", value.replace('"', "\"\""))
+    } else {
+        value.to_string()
+    }
+}
+
+pub fn to_csv(rows: &[Row]) -> String {
+    let mut csv = String::from(
+        "variant,heuristic,mode,runs,solved,time_p50_s,time_p95_s,expanded_p50,expanded_p95,peak_states_p50,peak_states_p95,peak_memory_p50_bytes,peak_memory_p95_bytes,length_p50,length_p95,error\n",
+    );
+    for row in rows {
+        let mut fields = vec![
+            csv_field(&row.variant),
+            csv_field(&row.heuristic),
+            csv_field(&row.mode),
+            row.runs.to_string(),
+            row.samples.len().to_string(),
+        ];
+        match row.summary() {
+            Some(summary) => {
+                for stat in [
+                    Some(summary.seconds),
+                    Some(summary.expanded),
+                    Some(summary.peak_states),
+                    summary.peak_memory,
+                    Some(summary.length),
+                ] {
+                    match stat {
+                        Some(stat) => {
+                            fields.push(stat.median.to_string());
+                            fields.push(stat.p95.to_string());
+                        }
+                        None => fields.extend(std::iter::repeat_n(String::new(), 2)),
+                    }
+                }
+            }
+            None => fields.extend(std::iter::repeat_n(String::new(), 10)),
+        }
+        fields.push(csv_field(row.error.as_deref().unwrap_or("")));
+        csv.push_str(&fields.join(","));
+        csv.push('\n');
+    }
+    csv
+}
+
+pub fn write_csv(path: &Path, rows: &[Row]) -> Result<(), String> {
+    fs::write(path, to_csv(rows))
+        .map_err(|err| format!("Failed to write {}: {}", path.display(), err))
+}
+
+#[test]
+fn percentiles() {
+    assert_eq!(percentile(&[], 0.5), None);
+    assert_eq!(percentile(&[4.], 0.95), Some(4.));
+    assert_eq!(percentile(&[3., 1., 2.], 0.5), Some(2.));
+    assert_eq!(percentile(&[4., 1., 3., 2.], 0.5), Some(2.5));
+    // Rank 0.95 * 19 = 18.05 of 1 to 20
+    let values: Vec<f64> = (1..=20).rev().map(f64::from).collect();
+    assert!((percentile(&values, 0.95).unwrap() - 19.05).abs() < 1e-9);
+}
+
+#[test]
+fn csv_rows() {
+    let mut solved = Row::new("ida*", "manhattan", "normal");
+    solved.runs = 2;
+    for (seconds, expanded) in [(0.5, 10), (1.5, 30)] {
+        solved.samples.push(Sample {
+            seconds,
+            expanded,
+            peak_states: 5,
+            peak_memory: Some(expanded << 20),
+            length: 20,
+        });
+    }
+    let mut unmeasured = Row::new("a*", "manhattan", "normal");
+    unmeasured.runs = 1;
+    unmeasured.samples.push(Sample {
+        seconds: 1.,
+        expanded: 10,
+        peak_states: 5,
+        peak_memory: None,
+        length: 20,
+    });
+    let mut failed = Row::new("lookup", "pdb", "normal");
+    failed.runs = 1;
+    failed.error = Some("Lookup tables only exist for 3x3 puzzles, not \"4x4\"".to_string());
+    let csv = to_csv(&[solved, unmeasured, failed]);
+    let lines: Vec<&str> = csv.lines().collect();
+    assert_eq!(lines.len(), 4);
+    assert_eq!(
+        lines[1],
+        "ida*,manhattan,normal,2,2,1,1.45,20,29,5,5,20971520,30408704,20,20,"
+    );
+    assert_eq!(lines[2], "a*,manhattan,normal,1,1,1,1,10,10,5,5,,,20,20,");
+    assert_eq!(
+        lines[3],
+        "lookup,pdb,normal,1,0,,,,,,,,,,,\"Lookup tables only exist for 3x3 puzzles, not \"\"4x4\"\"\""
+    );
+}
+
+#[test]
+fn measures_memory() {
+    let start = start_peak_memory();
+    if start.is_none() {
+        // Not on Linux
+        return;
+    }
+    drop(std::hint::black_box(vec![1u8; 64 << 20]));
+    // Some pages of the buffer may already be resident
+    assert!(peak_memory(start).unwrap() >= 32 << 20);
+}
@@ -0,0 +1,239 @@
+use crate::config::Config;
+use crate::puzzle::Puzzle;
+use npuzzle::{blank_moves, neighbors, Board, Heuristic, Mode, NodeWithCost, Solution};
+use std::collections::{BinaryHeap, HashMap, HashSet};
+use std::time::Instant;
+
+// One direction of the search, from its source to its target
+struct Frontier<'a> {
+    target: &'a Board,
+    open_set: BinaryHeap<NodeWithCost>,
+    closed: HashSet<Board>,
+    best_path_to_node: HashMap<Board, Board>,
+    best_cost_to_node: HashMap<Board, f64>,
+    expanded: usize,
+}
+
+impl<'a> Frontier<'a> {
+    fn new(source: &Board, target: &'a Board) -> Frontier<'a> {
+        let mut frontier = Frontier {
+            target,
+            open_set: BinaryHeap::new(),
+            closed: HashSet::new(),
+            best_path_to_node: HashMap::new(),
+            best_cost_to_node: HashMap::new(),
+            expanded: 0,
+        };
+        frontier.open_set.push(NodeWithCost {
+            cost: 0.,
+            node: source.clone(),
+        });
+        frontier.best_cost_to_node.insert(source.clone(), 0.);
+        frontier
+    }
+
+    // Lowest cost in the open set, entries that were already closed are kept and make it a lower bound
+    fn min_cost(&self) -> f64 {
+        self.open_set
+            .peek()
+            .map(|current| current.cost)
+            .unwrap_or(f64::INFINITY)
+    }
+
+    // Path from the source of the frontier to the node, including both
+    fn path_to(&self, node: &Board) -> Vec<Vec<i32>> {
+        let mut full_path = vec![node.to_vec()];
+        let mut current = node;
+        while let Some(previous) = self.best_path_to_node.get(current) {
+            full_path.push(previous.to_vec());
+            current = previous;
+        }
+        full_path.reverse();
+        full_path
+    }
+}
+
+struct Meeting {
+    cost: f64,
+    node: Board,
+}
+
+// Expand the best node of the frontier and update the best meeting point with the other frontier
+fn expand(
+    puzzle: &Puzzle,
+    mode: &Mode,
+    heuristic: Heuristic,
+    frontier: &mut Frontier,
+    other: &Frontier,
+    meeting: &mut Option<Meeting>,
+) {
+    let current = match frontier.open_set.pop() {
+        Some(current) => current,
+        None => return,
+    };
+    if !frontier.closed.insert(current.node.clone()) {
+        return;
+    }
+    frontier.expanded += 1;
+
+    let next_move_cost = frontier.best_cost_to_node[&current.node] + 1.;
+    for neighbor in neighbors(puzzle.size, &current.node).into_iter().flatten() {
+        if frontier.closed.contains(&neighbor) {
+            continue;
+        }
+        let neighbor_previous_cost = frontier.best_cost_to_node.get(&neighbor);
+        if neighbor_previous_cost.is_some() && next_move_cost >= *neighbor_previous_cost.unwrap() {
+            continue;
+        }
+        frontier
+            .best_path_to_node
+            .insert(neighbor.clone(), current.node.clone());
+        frontier
+            .best_cost_to_node
+            .insert(neighbor.clone(), next_move_cost);
+        // The neighbor was reached by the other direction, the path can be completed
+        if let Some(other_cost) = other.best_cost_to_node.get(&neighbor) {
+            let cost = next_move_cost + other_cost;
+            if meeting.is_none() || cost < meeting.as_ref().unwrap().cost {
+                *meeting = Some(Meeting {
+                    cost,
+                    node: neighbor.clone(),
+                });
+            }
+        }
+        frontier.open_set.push(NodeWithCost {
+            cost: mode.cost(
+                next_move_cost,
+                heuristic.evaluate(puzzle.size, &neighbor, frontier.target),
+            ),
+            node: neighbor,
+        });
+    }
+}
+
+pub fn solve(puzzle: &Puzzle, config: &Config, heuristic: Heuristic) -> Result<Solution, String> {
+    let now = Instant::now();
+    let mode = config.mode();
+
+    // Summary
+    let mut total_used_states = 0;
+    let mut biggest_state: usize = 2; // 2 are the initial and goal states
+
+    // The forward search goes from the map to the goal and the backward search from the goal to the map
+    let map = Board::from(&puzzle.map[..]);
+    let goal = Board::from(&puzzle.goal[..]);
+    let mut forward = Frontier::new(&map, &goal);
+    let mut backward = Frontier::new(&goal, &map);
+    let mut meeting: Option<Meeting> = None;
+    if map == goal {
+        meeting = Some(Meeting {
+            cost: 0.,
+            node: map.clone(),
+        });
+    }
+
+    while !forward.open_set.is_empty() || !backward.open_set.is_empty() || meeting.is_some() {
+        // Stop when no path through the open sets can be better than the best meeting point
+        // -- in greedy mode the first meeting point is used,
+        // -- and in uniform mode a new path would link a node of each open set
+        if let Some(current) = &meeting {
+            let done = match mode {
+                Mode::Greedy => true,
+                Mode::Uniform => current.cost <= forward.min_cost() + backward.min_cost() + 1.,
+                _ => current.cost <= forward.min_cost().max(backward.min_cost()),
+            };
+            if done {
+                let mut steps = forward.path_to(&current.node);
+                let mut backward_steps = backward.path_to(&current.node);
+                backward_steps.pop();
+                backward_steps.reverse();
+                steps.extend(backward_steps);
+                return Ok(Solution {
+                    total_used_states,
+                    biggest_state,
+                    moves: blank_moves(puzzle.size, &steps)?,
+                    steps,
+                    expanded_by_direction: Some((forward.expanded, backward.expanded)),
+                    suboptimality_bound: mode.suboptimality_bound(&heuristic),
+                    transposition_hits: None,
+                    layer_histogram: None,
+                });
+            }
+        }
+
+        // Always expand the smallest frontier
+        total_used_states += 1;
+        if !forward.open_set.is_empty()
+            && (backward.open_set.is_empty() || forward.open_set.len() <= backward.open_set.len())
+        {
+            expand(
+                puzzle,
+                &mode,
+                heuristic,
+                &mut forward,
+                &backward,
+                &mut meeting,
+            );
+        } else {
+            expand(
+                puzzle,
+                &mode,
+                heuristic,
+                &mut backward,
+                &forward,
+                &mut meeting,
+            );
+        }
+
+        let state = forward.open_set.len() + backward.open_set.len();
+        if state > biggest_state {
+            biggest_state = state;
+        }
+
+        if total_used_states % 100000 == 0 {
+            config.progress(&format!(
+                "#> Explored {} states ({} forward, {} backward) in {:.2?}",
+                total_used_states,
+                forward.expanded,
+                backward.expanded,
+                now.elapsed()
+            ));
+        }
+    }
+
+    Err(String::from("Failed to find a solution for this puzzle"))
+}
+
+#[test]
+fn finds_optimal_length() {
+    for (path, solution_type, optimal) in [
+        ("puzzles/solv_3_1.map", "snail", 26),
+        ("puzzles/solv_3_2_last.map", "last", 24),
+        ("puzzles/solv_3_3.map", "snail", 20),
+        ("puzzles/solv_3_4.map", "snail", 30),
+    ] {
+        let puzzle = Puzzle::new(path, solution_type).unwrap();
+        let solution = solve(&puzzle, &Config::default(), crate::heuristic::MANHATTAN).unwrap();
+        assert_eq!(solution.steps.len() - 1, optimal, "{}", path);
+        assert_eq!(solution.steps[0], puzzle.map);
+        assert_eq!(*solution.steps.last().unwrap(), puzzle.goal);
+        // Each step is a single move
+        for window in solution.steps.windows(2) {
+            let next = Some(Board::from(&window[1][..]));
+            assert!(neighbors(puzzle.size, &Board::from(&window[0][..])).contains(&next));
+        }
+    }
+}
+
+#[test]
+fn already_solved() {
+    let puzzle = Puzzle::new("puzzles/solv_3_1_first.map", "first").unwrap();
+    let solved = Puzzle {
+        size: puzzle.size,
+        height: puzzle.height,
+        map: puzzle.goal.clone(),
+        goal: puzzle.goal.clone(),
+    };
+    let solution = solve(&solved, &Config::default(), crate::heuristic::MANHATTAN).unwrap();
+    assert_eq!(solution.steps, vec![puzzle.goal]);
+}
@@ -0,0 +1,193 @@
+use std::fmt;
+
+// Cells are packed in a single integer when they fit, and in multiple words for bigger boards
+#[derive(Clone, PartialEq, Eq, Hash, PartialOrd, Ord)]
+enum Cells {
+    Small(u64),
+    Medium(u128),
+    Large(Box<[u64]>),
+}
+
+// Compact representation of the cells of a puzzle,
+// -- each cell uses only the bits needed for the biggest value (4 bits up to 4x4, 5 up to 5x5...),
+// -- the first cell is stored in the most significant bits so boards are ordered like their cells
+#[derive(Clone, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub struct Board {
+    cells: Cells,
+    len: u16,
+    bits: u8,
+    blank: u16,
+}
+
+impl Board {
+    pub fn new(cells: &[i32]) -> Board {
+        let max = cells.iter().copied().max().unwrap_or(0).max(1) as u32;
+        let bits = (u32::BITS - max.leading_zeros()) as usize;
+        let len = cells.len();
+        let storage = if len * bits <= 64 {
+            Cells::Small(0)
+        } else if len * bits <= 128 {
+            Cells::Medium(0)
+        } else {
+            let cells_per_word = 64 / bits;
+            Cells::Large(vec![0; len.div_ceil(cells_per_word)].into_boxed_slice())
+        };
+        let mut board = Board {
+            cells: storage,
+            len: len as u16,
+            bits: bits as u8,
+            blank: 0,
+        };
+        for (index, &cell) in cells.iter().enumerate() {
+            board.set(index, cell);
+            if cell == 0 {
+                board.blank = index as u16;
+            }
+        }
+        board
+    }
+
+    pub fn len(&self) -> usize {
+        self.len as usize
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    // Index of the empty cell
+    pub fn blank(&self) -> usize {
+        self.blank as usize
+    }
+
+    // Word and offset from the least significant bit of the cell at index
+    fn locate(&self, index: usize) -> (usize, usize) {
+        let bits = self.bits as usize;
+        match self.cells {
+            Cells::Large(_) => {
+                let cells_per_word = 64 / bits;
+                (
+                    index / cells_per_word,
+                    (cells_per_word - 1 - index % cells_per_word) * bits,
+                )
+            }
+            _ => (0, (self.len() - 1 - index) * bits),
+        }
+    }
+
+    pub fn get(&self, index: usize) -> i32 {
+        let bits = self.bits as usize;
+        let mask = (1 << bits) - 1;
+        match &self.cells {
+            Cells::Small(cells) => ((cells >> ((self.len() - 1 - index) * bits)) & mask) as i32,
+            Cells::Medium(cells) => {
+                ((cells >> ((self.len() - 1 - index) * bits)) as u64 & mask) as i32
+            }
+            Cells::Large(words) => {
+                let cells_per_word = 64 / bits;
+                let shift = (cells_per_word - 1 - index % cells_per_word) * bits;
+                ((words[index / cells_per_word] >> shift) & mask) as i32
+            }
+        }
+    }
+
+    fn set(&mut self, index: usize, value: i32) {
+        let (word, shift) = self.locate(index);
+        let mask: u64 = (1 << self.bits) - 1;
+        let value = value as u64;
+        match &mut self.cells {
+            Cells::Small(cells) => *cells = (*cells & !(mask << shift)) | (value << shift),
+            Cells::Medium(cells) => {
+                *cells = (*cells & !((mask as u128) << shift)) | ((value as u128) << shift)
+            }
+            Cells::Large(words) => {
+                words[word] = (words[word] & !(mask << shift)) | (value << shift)
+            }
+        }
+    }
+
+    // Move the tile at index to the empty cell
+    pub fn slide(&self, index: usize) -> Board {
+        let mut board = self.clone();
+        board.set(self.blank(), self.get(index));
+        board.set(index, 0);
+        board.blank = index as u16;
+        board
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = i32> + '_ {
+        (0..self.len()).map(|index| self.get(index))
+    }
+
+    pub fn position(&self, value: i32) -> Option<usize> {
+        self.iter().position(|cell| cell == value)
+    }
+
+    pub fn to_vec(&self) -> Vec<i32> {
+        self.iter().collect()
+    }
+}
+
+impl From<&[i32]> for Board {
+    fn from(cells: &[i32]) -> Board {
+        Board::new(cells)
+    }
+}
+
+impl From<Vec<i32>> for Board {
+    fn from(cells: Vec<i32>) -> Board {
+        Board::new(&cells)
+    }
+}
+
+impl fmt::Debug for Board {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_list().entries(self.iter()).finish()
+    }
+}
+
+#[test]
+fn round_trip() {
+    for size in 2..=8 {
+        let cells: Vec<i32> = (0..size * size).rev().collect();
+        let board = Board::from(&cells[..]);
+        assert_eq!(board.to_vec(), cells);
+        assert_eq!(board.blank(), cells.len() - 1);
+    }
+}
+
+#[test]
+fn storage_size() {
+    let four: Vec<i32> = (0..16).collect();
+    assert!(matches!(Board::from(four).cells, Cells::Small(_)));
+    let five: Vec<i32> = (0..25).collect();
+    assert!(matches!(Board::from(five).cells, Cells::Medium(_)));
+    let six: Vec<i32> = (0..36).collect();
+    assert!(matches!(Board::from(six).cells, Cells::Large(_)));
+}
+
+#[test]
+fn ordered_like_cells() {
+    let cells = [
+        vec![0, 1, 2, 3, 4, 5, 6, 7, 8],
+        vec![1, 0, 2, 3, 4, 5, 6, 7, 8],
+        vec![8, 7, 6, 5, 4, 3, 2, 1, 0],
+        vec![1, 2, 3, 4, 5, 6, 7, 8, 0],
+    ];
+    for left in cells.iter() {
+        for right in cells.iter() {
+            assert_eq!(
+                Board::from(&left[..]).cmp(&Board::from(&right[..])),
+                left.cmp(right)
+            );
+        }
+    }
+}
+
+#[test]
+fn slide_tile() {
+    let board = Board::from(vec![1, 2, 3, 8, 0, 4, 7, 6, 5]);
+    let next = board.slide(1);
+    assert_eq!(next.to_vec(), vec![1, 0, 3, 8, 2, 4, 7, 6, 5]);
+    assert_eq!(next.blank(), 1);
+}
@@ -1,33 +1,112 @@
+use crate::{bench, disk_bfs, goal, sma_star, transposition_table};
+use npuzzle::Mode;
 use std::env;
 use std::process;
 
-#[derive(Debug)]
+// Weight of h(n) in weighted mode when no --weight is given
+pub const DEFAULT_WEIGHT: f64 = 1.5;
+
+// Directory of the lookup tables written by the enumerate command when no --lookup-dir is given
+pub const DEFAULT_LOOKUP_DIR: &str = "lookup";
+
+// Commands that can be given as the first argument instead of solving puzzles
+const COMMANDS: [&str; 4] = ["enumerate", "check-heuristics", "verify", "bench"];
+
+// Every mode that can be given to --mode
+pub const MODES: [&str; 4] = ["normal", "greedy", "uniform", "weighted"];
+
+#[derive(Clone, Debug)]
 pub struct Config {
+    pub command: Option<String>,
     pub variant: String,
     pub heuristic_name: String,
     pub solution_type: String,
+    pub goal_file: Option<String>,
+    // Goal read from the goal file, which also gives the size of the generated puzzles
+    pub goal: Option<Vec<i32>>,
     pub mode: String,
+    // Every board of the solution, or only its moves
+    pub output: String,
+    // Lines for humans, or one JSON object per puzzle
+    pub format: String,
     pub files: Vec<String>,
     pub solvable: bool,
     pub amount: u32,
+    // Seed of the generated puzzles, random unless --seed is given and printed to generate them again
+    pub seed: u64,
+    // Columns of the generated puzzles
     pub size: i32,
+    // Rows of the generated puzzles, the same as the columns unless --size=RxC is given
+    pub height: i32,
+    pub pdb_dir: Option<String>,
+    pub weight: Option<f64>,
+    pub time_limit: Option<f64>,
+    pub move_pruning: String,
+    pub threads: usize,
+    // Size of the IDA* transposition table in bytes
+    pub tt_size: Option<usize>,
+    pub max_nodes: Option<usize>,
+    pub work_dir: Option<String>,
+    // Layers counted by disk-bfs, up to the goal or every reachable one
+    pub histogram: String,
+    pub lookup_dir: Option<String>,
+    // Distance of the boards compared by the check-heuristics command other than 3x3
+    pub max_distance: Option<usize>,
+    // Runs of each puzzle for each combination of the bench command
+    pub runs: usize,
+    pub csv: Option<String>,
+    // Hide the progress of the searches, set by the bench command
+    pub quiet: bool,
 }
 
-impl Config {
-    pub fn new() -> Result<Config, String> {
-        let args: Vec<String> = env::args().skip(1).collect();
-
-        // Parse each arguments as option or puzzle path
-        let mut config = Config {
+impl Default for Config {
+    fn default() -> Config {
+        Config {
+            command: None,
             variant: "ida*".to_string(),
             heuristic_name: "linear-conflicts".to_string(),
             solution_type: "snail".to_string(),
+            goal_file: None,
+            goal: None,
             mode: "normal".to_string(),
+            output: "boards".to_string(),
+            format: "text".to_string(),
             files: Vec::new(),
             solvable: true,
             amount: 1,
+            seed: rand::random(),
             size: 3,
-        };
+            height: 3,
+            pdb_dir: None,
+            weight: None,
+            time_limit: None,
+            move_pruning: "inverse".to_string(),
+            threads: 1,
+            tt_size: None,
+            max_nodes: None,
+            work_dir: None,
+            histogram: "goal".to_string(),
+            lookup_dir: None,
+            max_distance: None,
+            runs: bench::DEFAULT_RUNS,
+            csv: None,
+            quiet: false,
+        }
+    }
+}
+
+impl Config {
+    pub fn new() -> Result<Config, String> {
+        let mut args: Vec<String> = env::args().skip(1).collect();
+
+        // Parse each arguments as option or puzzle path
+        let mut config = Config::default();
+        if args
+            .first()
+            .is_some_and(|arg| COMMANDS.contains(&arg.as_str()))
+        {
+            config.command = Some(args.remove(0));
+        }
         let mut found_first_puzzle = false;
         for arg in args.iter() {
             if !found_first_puzzle && arg.starts_with("--") {
@@ -39,8 +118,16 @@ impl Config {
                         config.heuristic_name = value.to_string();
                     } else if option_name == "--solution-type" {
                         config.solution_type = value.to_string();
+                    } else if option_name == "--goal-file" {
+                        config.goal_file = Some(value.to_string());
                     } else if option_name == "--mode" {
                         config.mode = value.to_string();
+                    } else if option_name == "--output" {
+                        config.output = value.to_string();
+                    } else if option_name == "--format" {
+                        config.format = value.to_string();
+                    } else if option_name == "--move-pruning" {
+                        config.move_pruning = value.to_string();
                     } else if option_name == "--unsolvable" {
                         config.solvable = false;
                     } else if option_name == "--amount" {
@@ -49,16 +136,117 @@ impl Config {
                             return Err(format!("Invalid amount `{}`: {}", value, err));
                         }
                         config.amount = amount.unwrap();
+                    } else if option_name == "--seed" {
+                        let seed = value.to_string().parse();
+                        if let Err(err) = seed {
+                            return Err(format!("Invalid seed `{}`: {}", value, err));
+                        }
+                        config.seed = seed.unwrap();
                     } else if option_name == "--size" {
-                        let size = value.to_string().parse();
-                        if let Err(err) = size {
-                            return Err(format!("Invalid size `{}`: {}", value, err));
+                        if let Some((rows, columns)) = value.split_once('x') {
+                            let parse = |side: &str| {
+                                side.parse::<i32>()
+                                    .map_err(|err| format!("Invalid size `{}`: {}", value, err))
+                            };
+                            let (rows, columns) = (parse(rows)?, parse(columns)?);
+                            if rows < 2 || columns < 2 || rows.max(columns) < 3 {
+                                return Err(format!(
+                                    "Invalid size {}, each side must be at least 2 and one at least 3",
+                                    value
+                                ));
+                            }
+                            config.size = columns;
+                            config.height = rows;
+                        } else {
+                            let size = value.to_string().parse();
+                            if let Err(err) = size {
+                                return Err(format!("Invalid size `{}`: {}", value, err));
+                            }
+                            let size = size.unwrap();
+                            if size < 3 {
+                                return Err(format!(
+                                    "Invalid amount {}, must be at least 3",
+                                    value
+                                ));
+                            }
+                            config.size = size;
+                            config.height = size;
+                        }
+                    } else if option_name == "--work-dir" {
+                        config.work_dir = Some(value.to_string());
+                    } else if option_name == "--histogram" {
+                        config.histogram = value.to_string();
+                    } else if option_name == "--lookup-dir" {
+                        config.lookup_dir = Some(value.to_string());
+                    } else if option_name == "--pdb-dir" {
+                        config.pdb_dir = Some(value.to_string());
+                    } else if option_name == "--weight" {
+                        let weight = value.to_string().parse();
+                        if let Err(err) = weight {
+                            return Err(format!("Invalid weight `{}`: {}", value, err));
+                        }
+                        let weight = weight.unwrap();
+                        if weight < 1. {
+                            return Err(format!("Invalid weight {}, must be at least 1", value));
+                        }
+                        config.weight = Some(weight);
+                    } else if option_name == "--time-limit" {
+                        let time_limit = value.to_string().parse();
+                        if let Err(err) = time_limit {
+                            return Err(format!("Invalid time limit `{}`: {}", value, err));
+                        }
+                        let time_limit = time_limit.unwrap();
+                        if time_limit <= 0. {
+                            return Err(format!("Invalid time limit {}, must be positive", value));
+                        }
+                        config.time_limit = Some(time_limit);
+                    } else if option_name == "--threads" {
+                        let threads = value.to_string().parse();
+                        if let Err(err) = threads {
+                            return Err(format!("Invalid threads `{}`: {}", value, err));
+                        }
+                        let threads = threads.unwrap();
+                        if threads < 1 {
+                            return Err(format!("Invalid threads {}, must be at least 1", value));
+                        }
+                        config.threads = threads;
+                    } else if option_name == "--tt-size" {
+                        let tt_size = transposition_table::parse_size(value);
+                        if let Err(err) = tt_size {
+                            return Err(format!(
+                                "Invalid transposition table size `{}`: {}",
+                                value, err
+                            ));
                         }
-                        let size = size.unwrap();
-                        if size < 3 {
-                            return Err(format!("Invalid amount {}, must be at least 3", value));
+                        config.tt_size = Some(tt_size.unwrap());
+                    } else if option_name == "--max-nodes" {
+                        let max_nodes = value.to_string().parse();
+                        if let Err(err) = max_nodes {
+                            return Err(format!("Invalid max nodes `{}`: {}", value, err));
                         }
-                        config.size = size;
+                        let max_nodes = max_nodes.unwrap();
+                        if max_nodes < 2 {
+                            return Err(format!("Invalid max nodes {}, must be at least 2", value));
+                        }
+                        config.max_nodes = Some(max_nodes);
+                    } else if option_name == "--max-distance" {
+                        let max_distance = value.to_string().parse();
+                        if let Err(err) = max_distance {
+                            return Err(format!("Invalid max distance `{}`: {}", value, err));
+                        }
+                        config.max_distance = Some(max_distance.unwrap());
+                    } else if option_name == "--runs" {
+                        let runs = value.to_string().parse();
+                        if let Err(err) = runs {
+                            return Err(format!("Invalid runs `{}`: {}", value, err));
+                        }
+                        let runs = runs.unwrap();
+                        if runs < 1 {
+                            return Err(format!("Invalid runs {}, must be at least 1", value));
+                        }
+                        config.runs = runs;
+                    } else if option_name == "--csv" {
+                        config.csv = Some(value.to_string());
                     }
                 } else {
                     return Err(format!("Malformed argument {}", arg));
@@ -71,11 +259,56 @@ impl Config {
             }
         }
 
+        if let Some(goal_file) = &config.goal_file {
+            let (size, height, goal) = goal::load(goal_file)?;
+            config.size = size;
+            config.height = height;
+            config.goal = Some(goal);
+        }
+
         Ok(config)
     }
 
+    // Name of the goal of the puzzles, the goal file or the solution type
+    pub fn goal_name(&self) -> &str {
+        self.goal_file.as_deref().unwrap_or(&self.solution_type)
+    }
+
+    pub fn mode(&self) -> Mode {
+        match self.mode.as_str() {
+            "greedy" => Mode::Greedy,
+            "uniform" => Mode::Uniform,
+            "weighted" => Mode::Weighted(self.weight.unwrap_or(DEFAULT_WEIGHT)),
+            _ => Mode::Normal,
+        }
+    }
+
+    // Progress of a search, written on the error output when the standard output is JSON
+    pub fn progress(&self, message: &str) {
+        if self.quiet {
+            return;
+        }
+        if self.format == "json" {
+            eprintln!("{}", message);
+        } else {
+            println!("{}", message);
+        }
+    }
+
     pub fn check_and_explain(&self) {
-        if ![String::from("ida*"), String::from("a*")].contains(&self.variant) {
+        if ![
+            String::from("ida*"),
+            String::from("a*"),
+            String::from("bidir-a*"),
+            String::from("ara*"),
+            String::from("hda*"),
+            String::from("sma*"),
+            String::from("disk-bfs"),
+            String::from("lookup"),
+            String::from("reduction"),
+        ]
+        .contains(&self.variant)
+        {
             eprintln!("Unknown variant: {}", self.variant);
             process::exit(1);
         }
@@ -89,24 +322,85 @@ impl Config {
             eprintln!("Unknown solution type: {}", self.solution_type);
             process::exit(1);
         }
-        if ![
-            String::from("normal"),
-            String::from("greedy"),
-            String::from("uniform"),
-        ]
-        .contains(&self.mode)
-        {
+        if !MODES.contains(&self.mode.as_str()) {
             eprintln!("Unknown mode: {}", self.mode);
             process::exit(1);
         }
+        if ![String::from("inverse"), String::from("fsm")].contains(&self.move_pruning) {
+            eprintln!("Unknown move pruning: {}", self.move_pruning);
+            process::exit(1);
+        }
+        if ![String::from("boards"), String::from("moves")].contains(&self.output) {
+            eprintln!("Unknown output: {}", self.output);
+            process::exit(1);
+        }
+        if ![String::from("text"), String::from("json")].contains(&self.format) {
+            eprintln!("Unknown format: {}", self.format);
+            process::exit(1);
+        }
+        if ![String::from("goal"), String::from("full")].contains(&self.histogram) {
+            eprintln!("Unknown histogram: {}", self.histogram);
+            process::exit(1);
+        }
+        if self.format == "json" {
+            return;
+        }
         println!("###");
         println!("Variant:             {}", self.variant);
         println!("Heuristic:           {}", self.heuristic_name);
-        println!("Solution type:       {}", self.solution_type);
+        match &self.goal_file {
+            Some(goal_file) => println!("Goal file:           {}", goal_file),
+            None => println!("Solution type:       {}", self.solution_type),
+        }
         println!("Mode:                {}", self.mode);
+        println!("Output:              {}", self.output);
+        if self.mode == "weighted" || self.weight.is_some() {
+            println!(
+                "Weight:              {}",
+                self.weight.unwrap_or(DEFAULT_WEIGHT)
+            );
+        }
+        if self.variant == "ida*" {
+            println!("Move pruning:        {}", self.move_pruning);
+            if let Some(tt_size) = self.tt_size {
+                println!("Transposition table: {} bytes", tt_size);
+            }
+        }
+        if self.variant == "ida*" || self.variant == "hda*" {
+            println!("Threads:             {}", self.threads);
+        }
+        if self.variant == "sma*" {
+            println!(
+                "Max nodes:           {}",
+                self.max_nodes.unwrap_or(sma_star::DEFAULT_MAX_NODES)
+            );
+        }
+        if self.variant == "disk-bfs" {
+            println!(
+                "Work directory:      {}",
+                self.work_dir
+                    .clone()
+                    .unwrap_or_else(|| disk_bfs::default_work_dir().display().to_string())
+            );
+            println!("Histogram:           {}", self.histogram);
+        }
+        if let Some(time_limit) = self.time_limit {
+            println!("Time limit:          {}s", time_limit);
+        }
+        if let Some(pdb_dir) = &self.pdb_dir {
+            println!("PDB directory:       {}", pdb_dir);
+        }
+        if let Some(lookup_dir) = &self.lookup_dir {
+            println!("Lookup directory:    {}", lookup_dir);
+        }
         if self.files.is_empty() {
-            println!("(Generate) Size:     {}", self.size);
+            if self.height == self.size {
+                println!("(Generate) Size:     {}", self.size);
+            } else {
+                println!("(Generate) Size:     {}x{}", self.height, self.size);
+            }
             println!("(Generate) Amount:   {}", self.amount);
+            println!("(Generate) Seed:     {}", self.seed);
             println!("(Generate) Solvable: {}", self.solvable);
         }
         println!("###");
@@ -0,0 +1,381 @@
+use crate::config::Config;
+use crate::puzzle::Puzzle;
+use npuzzle::{blank_moves, neighbors, Board, Heuristic, Solution};
+use std::cmp::{Ordering, Reverse};
+use std::collections::BinaryHeap;
+use std::fs::{self, File};
+use std::io::{BufReader, BufWriter, Read, Seek, SeekFrom, Write};
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicUsize, Ordering as AtomicOrdering};
+use std::time::Instant;
+
+// Amount of states sorted in memory before being written to a run file
+const RUN_SIZE: usize = 1 << 20;
+
+// Searches started by this process, so each one has its own directory
+static SEARCHES: AtomicUsize = AtomicUsize::new(0);
+
+// States are written with one byte per cell, so the files are sorted like the boards
+// -- and the tiles must fit in a byte
+pub const MAX_CELLS: usize = 256;
+
+fn encode(board: &Board) -> Vec<u8> {
+    board.iter().map(|cell| cell as u8).collect()
+}
+
+fn decode(record: &[u8]) -> Board {
+    Board::from(record.iter().map(|&cell| cell as i32).collect::<Vec<i32>>())
+}
+
+// Sequential reader of a file of fixed size records
+struct Records {
+    reader: BufReader<File>,
+    len: usize,
+}
+
+impl Records {
+    fn open(path: &Path, len: usize) -> Result<Records, String> {
+        let file = File::open(path)
+            .map_err(|err| format!("Failed to open {}: {}", path.display(), err))?;
+        Ok(Records {
+            reader: BufReader::new(file),
+            len,
+        })
+    }
+
+    fn next(&mut self) -> Result<Option<Vec<u8>>, String> {
+        let mut record = vec![0; self.len];
+        match self.reader.read_exact(&mut record) {
+            Ok(()) => Ok(Some(record)),
+            Err(err) if err.kind() == std::io::ErrorKind::UnexpectedEof => Ok(None),
+            Err(err) => Err(format!("Failed to read a layer: {}", err)),
+        }
+    }
+}
+
+fn create(path: &Path) -> Result<BufWriter<File>, String> {
+    let file = File::create(path)
+        .map_err(|err| format!("Failed to create {}: {}", path.display(), err))?;
+    Ok(BufWriter::new(file))
+}
+
+fn write_record(writer: &mut BufWriter<File>, record: &[u8]) -> Result<(), String> {
+    writer
+        .write_all(record)
+        .map_err(|err| format!("Failed to write a layer: {}", err))
+}
+
+// Breadth-first search where each layer is a sorted file of the states at the same distance,
+// -- the next layer is generated in sorted runs that are merged while removing duplicates,
+// -- since each move changes the parity of the empty cell, a state of the next layer
+// -- can only be a duplicate of the next layer itself or of the previous layer,
+// -- the files are in a directory of the search inside work_dir so that
+// -- several processes or searches can share the same work_dir
+pub struct Layers {
+    work_dir: PathBuf,
+    size: i32,
+    len: usize,
+    // Amount of states at each distance, only up to the last generated layer
+    pub histogram: Vec<usize>,
+}
+
+impl Layers {
+    pub fn new(work_dir: &Path, size: i32, start: &Board) -> Result<Layers, String> {
+        let work_dir = work_dir.join(format!(
+            "search-{}-{}",
+            std::process::id(),
+            SEARCHES.fetch_add(1, AtomicOrdering::Relaxed)
+        ));
+        fs::create_dir_all(&work_dir)
+            .map_err(|err| format!("Failed to create {}: {}", work_dir.display(), err))?;
+        let layers = Layers {
+            work_dir,
+            size,
+            len: start.len(),
+            histogram: vec![1],
+        };
+        let mut writer = create(&layers.layer_path(0))?;
+        write_record(&mut writer, &encode(start))?;
+        writer
+            .flush()
+            .map_err(|err| format!("Failed to write a layer: {}", err))?;
+        Ok(layers)
+    }
+
+    fn layer_path(&self, depth: usize) -> PathBuf {
+        self.work_dir.join(format!("layer-{}.bin", depth))
+    }
+
+    fn run_path(&self, index: usize) -> PathBuf {
+        self.work_dir.join(format!("run-{}.bin", index))
+    }
+
+    fn write_run(&self, run: &mut Vec<Vec<u8>>, index: usize) -> Result<(), String> {
+        run.sort_unstable();
+        run.dedup();
+        let mut writer = create(&self.run_path(index))?;
+        for record in run.iter() {
+            write_record(&mut writer, record)?;
+        }
+        writer
+            .flush()
+            .map_err(|err| format!("Failed to write a run: {}", err))?;
+        run.clear();
+        Ok(())
+    }
+
+    // Generate the next layer and return true if it contains the target
+    pub fn expand(&mut self, target: Option<&Board>) -> Result<bool, String> {
+        let depth = self.histogram.len() - 1;
+
+        // Sorted runs of every neighbor of the last layer
+        let mut runs = 0;
+        let mut run = Vec::with_capacity(RUN_SIZE);
+        let mut current = Records::open(&self.layer_path(depth), self.len)?;
+        while let Some(record) = current.next()? {
+            let node = decode(&record);
+            for neighbor in neighbors(self.size, &node).into_iter().flatten() {
+                run.push(encode(&neighbor));
+            }
+            if run.len() >= RUN_SIZE {
+                self.write_run(&mut run, runs)?;
+                runs += 1;
+            }
+        }
+        if !run.is_empty() {
+            self.write_run(&mut run, runs)?;
+            runs += 1;
+        }
+
+        // Merge the runs, skipping duplicates and the states of the previous layer
+        let mut readers = (0..runs)
+            .map(|index| Records::open(&self.run_path(index), self.len))
+            .collect::<Result<Vec<Records>, String>>()?;
+        let mut heap = BinaryHeap::new();
+        for (index, reader) in readers.iter_mut().enumerate() {
+            if let Some(record) = reader.next()? {
+                heap.push(Reverse((record, index)));
+            }
+        }
+        let mut previous = match depth {
+            0 => None,
+            _ => Some(Records::open(&self.layer_path(depth - 1), self.len)?),
+        };
+        let mut previous_record = match previous.as_mut() {
+            Some(previous) => previous.next()?,
+            None => None,
+        };
+        let target = target.map(encode);
+        let mut found = false;
+        let mut count = 0;
+        let mut last: Option<Vec<u8>> = None;
+        let mut writer = create(&self.layer_path(depth + 1))?;
+        while let Some(Reverse((record, index))) = heap.pop() {
+            if let Some(next) = readers[index].next()? {
+                heap.push(Reverse((next, index)));
+            }
+            if last.as_ref() == Some(&record) {
+                continue;
+            }
+            while previous_record
+                .as_ref()
+                .is_some_and(|previous_record| previous_record < &record)
+            {
+                previous_record = previous.as_mut().unwrap().next()?;
+            }
+            if previous_record.as_ref() != Some(&record) {
+                write_record(&mut writer, &record)?;
+                count += 1;
+                found = found || target.as_ref() == Some(&record);
+            }
+            last = Some(record);
+        }
+        writer
+            .flush()
+            .map_err(|err| format!("Failed to write a layer: {}", err))?;
+        for index in 0..runs {
+            let _ = fs::remove_file(self.run_path(index));
+        }
+        self.histogram.push(count);
+        Ok(found)
+    }
+
+    // Binary search of a state in the sorted file of a layer
+    fn contains(&self, depth: usize, board: &Board) -> Result<bool, String> {
+        let path = self.layer_path(depth);
+        let mut file = File::open(&path)
+            .map_err(|err| format!("Failed to open {}: {}", path.display(), err))?;
+        let target = encode(board);
+        let mut record = vec![0; self.len];
+        let (mut low, mut high) = (0, self.histogram[depth]);
+        while low < high {
+            let middle = (low + high) / 2;
+            file.seek(SeekFrom::Start((middle * self.len) as u64))
+                .and_then(|_| file.read_exact(&mut record))
+                .map_err(|err| format!("Failed to read {}: {}", path.display(), err))?;
+            match record.cmp(&target) {
+                Ordering::Less => low = middle + 1,
+                Ordering::Greater => high = middle,
+                Ordering::Equal => return Ok(true),
+            }
+        }
+        Ok(false)
+    }
+
+    // Path from the start to a state of the last layer, going back one layer at a time
+    pub fn path_to(&self, target: &Board) -> Result<Vec<Vec<i32>>, String> {
+        let mut current = target.clone();
+        let mut full_path = vec![current.to_vec()];
+        for depth in (0..self.histogram.len() - 1).rev() {
+            let mut previous = None;
+            for neighbor in neighbors(self.size, &current).into_iter().flatten() {
+                if self.contains(depth, &neighbor)? {
+                    previous = Some(neighbor);
+                    break;
+                }
+            }
+            current = previous.ok_or_else(|| format!("Layer {} is missing a state", depth))?;
+            full_path.push(current.to_vec());
+        }
+        full_path.reverse();
+        Ok(full_path)
+    }
+
+    pub fn is_exhausted(&self) -> bool {
+        self.histogram.last() == Some(&0)
+    }
+
+    // Remove the directory of the search with every layer and run file
+    pub fn clean(&self) {
+        let _ = fs::remove_dir_all(&self.work_dir);
+    }
+}
+
+pub fn default_work_dir() -> PathBuf {
+    std::env::temp_dir().join("npuzzle-disk-bfs")
+}
+
+// Breadth-first search from the map until the layer containing the goal, ignoring the heuristic,
+// -- or until every reachable state is found for the full layer histogram
+pub fn solve(puzzle: &Puzzle, config: &Config, _: Heuristic) -> Result<Solution, String> {
+    if puzzle.map.len() > MAX_CELLS {
+        return Err(format!(
+            "Disk BFS can't be used for puzzles with more than {} cells",
+            MAX_CELLS
+        ));
+    }
+    let now = Instant::now();
+    let map = Board::from(&puzzle.map[..]);
+    let goal = Board::from(&puzzle.goal[..]);
+    let work_dir = config
+        .work_dir
+        .as_ref()
+        .map(PathBuf::from)
+        .unwrap_or_else(default_work_dir);
+
+    let mut layers = Layers::new(&work_dir, puzzle.size, &map)?;
+    let mut found = map == goal;
+    while !found && !layers.is_exhausted() {
+        found = match layers.expand(Some(&goal)) {
+            Ok(found) => found,
+            Err(err) => {
+                layers.clean();
+                return Err(err);
+            }
+        };
+        config.progress(&format!(
+            "#> Layer {} has {} states, found in {:.2?}",
+            layers.histogram.len() - 1,
+            layers.histogram.last().unwrap(),
+            now.elapsed()
+        ));
+    }
+    // The path is found from the last layer, before the next ones are generated
+    let mut steps = if found {
+        layers.path_to(&goal)
+    } else {
+        Err(String::from("Failed to find a solution for this puzzle"))
+    };
+    if config.histogram == "full" {
+        while steps.is_ok() && !layers.is_exhausted() {
+            if let Err(err) = layers.expand(None) {
+                steps = Err(err);
+                break;
+            }
+            config.progress(&format!(
+                "#> Layer {} has {} states, found in {:.2?}",
+                layers.histogram.len() - 1,
+                layers.histogram.last().unwrap(),
+                now.elapsed()
+            ));
+        }
+        // The last layer is the empty one after the farthest states
+        if layers.is_exhausted() {
+            layers.histogram.pop();
+        }
+    }
+    layers.clean();
+    let steps = steps?;
+
+    Ok(Solution {
+        total_used_states: layers.histogram.iter().sum(),
+        biggest_state: *layers.histogram.iter().max().unwrap(),
+        moves: blank_moves(puzzle.size, &steps)?,
+        steps,
+        expanded_by_direction: None,
+        suboptimality_bound: Some(1.),
+        transposition_hits: None,
+        layer_histogram: Some(layers.histogram),
+    })
+}
+
+#[test]
+fn finds_optimal_length() {
+    let work_dir = std::env::temp_dir().join("npuzzle-test-disk-bfs");
+    let config = Config {
+        work_dir: Some(work_dir.to_string_lossy().to_string()),
+        ..Config::default()
+    };
+    crate::puzzle::check_solutions(1, |puzzle| {
+        let solution = solve(puzzle, &config, crate::heuristic::MANHATTAN).unwrap();
+        let histogram = solution.layer_histogram.as_ref().unwrap();
+        assert_eq!(histogram.len(), solution.steps.len());
+        assert_eq!(histogram[0], 1);
+        solution
+    });
+    // Every search removed its own directory
+    assert_eq!(fs::read_dir(&work_dir).unwrap().count(), 0);
+    let _ = fs::remove_dir_all(work_dir);
+}
+
+#[test]
+fn full_histogram() {
+    let work_dir = std::env::temp_dir().join("npuzzle-test-disk-bfs-full");
+    let config = Config {
+        work_dir: Some(work_dir.to_string_lossy().to_string()),
+        histogram: "full".to_string(),
+        ..Config::default()
+    };
+    let puzzle = Puzzle::new("puzzles/solv_3_1.map", "snail").unwrap();
+    let solution = solve(&puzzle, &config, crate::heuristic::MANHATTAN).unwrap();
+    assert_eq!(solution.steps.len() - 1, 26);
+    // Half of the 9! boards can be reached, the farthest ones 31 moves away
+    let histogram = solution.layer_histogram.unwrap();
+    assert_eq!(histogram.iter().sum::<usize>(), 181440);
+    assert_eq!(histogram.len(), 32);
+    let _ = fs::remove_dir_all(work_dir);
+}
+
+#[test]
+fn tiles_fit_in_a_byte() {
+    let cells: Vec<i32> = (0..17 * 16).collect();
+    let puzzle = Puzzle {
+        size: 17,
+        height: 16,
+        map: cells.clone(),
+        goal: cells,
+    };
+    assert!(solve(&puzzle, &Config::default(), crate::heuristic::MANHATTAN).is_err());
+    let board = Board::from((0..MAX_CELLS as i32).rev().collect::<Vec<i32>>());
+    assert_eq!(decode(&encode(&board)), board);
+}
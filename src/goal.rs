@@ -1,9 +1,10 @@
+use crate::puzzle::Puzzle;
 use npuzzle::SnailIterator;
 
-pub fn generate_snail(size: i32) -> Result<Vec<i32>, String> {
-    let puzzle_size = size * size;
+pub fn generate_snail(width: i32, height: i32) -> Result<Vec<i32>, String> {
+    let puzzle_size = width * height;
     let mut solution: Vec<i32> = (1..=puzzle_size).collect();
-    let mut iterator = SnailIterator::new(size);
+    let mut iterator = SnailIterator::rectangle(width, height);
     // Iterate for each cells to add each numbers in "snail" order
     for (index, value) in iterator.by_ref() {
         let v: &mut i32 = &mut solution[index];
@@ -15,15 +16,15 @@ pub fn generate_snail(size: i32) -> Result<Vec<i32>, String> {
     Ok(solution)
 }
 
-pub fn generate_first(size: i32) -> Result<Vec<i32>, String> {
-    let puzzle_size = size * size;
+pub fn generate_first(width: i32, height: i32) -> Result<Vec<i32>, String> {
+    let puzzle_size = width * height;
     let mut solution: Vec<i32> = (1..puzzle_size).collect();
     solution.insert(0, 0);
     Ok(solution)
 }
 
-pub fn generate_last(size: i32) -> Result<Vec<i32>, String> {
-    let puzzle_size = size * size;
+pub fn generate_last(width: i32, height: i32) -> Result<Vec<i32>, String> {
+    let puzzle_size = width * height;
     let mut solution: Vec<i32> = (1..puzzle_size).collect();
     solution.push(0);
     Ok(solution)
@@ -33,17 +34,45 @@ pub fn generate(size: i32, solution_type: &str) -> Result<Vec<i32>, String> {
     if size < 3 {
         return Err(format!("Invalid size {}, must be at least 3", size));
     }
+    generate_rectangle(size, size, solution_type)
+}
+
+// Goal of a board with width columns and height rows
+pub fn generate_rectangle(
+    width: i32,
+    height: i32,
+    solution_type: &str,
+) -> Result<Vec<i32>, String> {
+    if width < 2 || height < 2 || width.max(height) < 3 {
+        return Err(format!(
+            "Invalid size {}x{}, each side must be at least 2 and one at least 3",
+            height, width
+        ));
+    }
 
     if solution_type == "snail" {
-        return generate_snail(size);
+        return generate_snail(width, height);
     } else if solution_type == "first" {
-        return generate_first(size);
+        return generate_first(width, height);
     } else if solution_type == "last" {
-        return generate_last(size);
+        return generate_last(width, height);
     }
     Err(format!("Unknown solution type `{}`", solution_type))
 }
 
+// Any valid map used as the goal, with its columns and rows
+pub fn load(path: &str) -> Result<(i32, i32, Vec<i32>), String> {
+    let (width, height, goal) =
+        Puzzle::read(path).map_err(|err| format!("Invalid goal file {}: {}", path, err))?;
+    if width < 2 || height < 2 || width.max(height) < 3 {
+        return Err(format!(
+            "Invalid goal size {}x{}, each side must be at least 2 and one at least 3",
+            height, width
+        ));
+    }
+    Ok((width, height, goal))
+}
+
 #[test]
 fn snail_generate_goal_2() {
     let solution = generate(2, &String::from("snail"));
@@ -76,3 +105,23 @@ fn snail_generate_goal_5() {
         ])
     )
 }
+
+#[test]
+fn snail_generate_rectangle() {
+    // 2 rows of 4 columns
+    assert_eq!(
+        generate_rectangle(4, 2, "snail"),
+        Ok(vec![1, 2, 3, 4, 0, 7, 6, 5])
+    );
+    // 3 rows of 5 columns
+    assert_eq!(
+        generate_rectangle(5, 3, "snail"),
+        Ok(vec![1, 2, 3, 4, 5, 12, 13, 14, 0, 6, 11, 10, 9, 8, 7])
+    );
+    // 5 rows of 3 columns
+    assert_eq!(
+        generate_rectangle(3, 5, "snail"),
+        Ok(vec![1, 2, 3, 12, 13, 4, 11, 14, 5, 10, 0, 6, 9, 8, 7])
+    );
+    assert!(generate_rectangle(2, 2, "snail").is_err());
+}
@@ -0,0 +1,305 @@
+use crate::config::Config;
+use crate::puzzle::Puzzle;
+use npuzzle::{blank_moves, neighbors, Board, Heuristic, Mode, NodeWithCost, Solution};
+use std::collections::hash_map::DefaultHasher;
+use std::collections::{BinaryHeap, HashMap};
+use std::hash::{Hash, Hasher};
+use std::sync::atomic::{AtomicBool, AtomicU64, AtomicUsize, Ordering};
+use std::sync::mpsc::{self, Receiver, Sender};
+use std::thread;
+use std::time::Duration;
+
+// Node sent to the thread that owns it
+struct Message {
+    node: Board,
+    parent: Board,
+    g: f64,
+    h: f64,
+}
+
+// Everything shared by the threads
+struct Shared<'a> {
+    puzzle: &'a Puzzle,
+    goal: &'a Board,
+    heuristic: Heuristic,
+    mode: &'a Mode,
+    threads: usize,
+    // Cost of the best solution found, as the bits of a f64
+    incumbent: AtomicU64,
+    // Threads that are working and messages that were not received yet,
+    // -- the search is over when both are zero
+    work: AtomicUsize,
+    // Set in greedy mode when the first solution is found
+    stop: AtomicBool,
+}
+
+impl Shared<'_> {
+    fn owner(&self, node: &Board) -> usize {
+        let mut hasher = DefaultHasher::new();
+        node.hash(&mut hasher);
+        (hasher.finish() % self.threads as u64) as usize
+    }
+
+    fn incumbent(&self) -> f64 {
+        f64::from_bits(self.incumbent.load(Ordering::SeqCst))
+    }
+
+    fn improve_incumbent(&self, cost: f64) {
+        let _ = self
+            .incumbent
+            .fetch_update(Ordering::SeqCst, Ordering::SeqCst, |current| {
+                if cost < f64::from_bits(current) {
+                    Some(cost.to_bits())
+                } else {
+                    None
+                }
+            });
+    }
+
+    // A node is useless if it can't lead to a solution better than the incumbent,
+    // -- in weighted mode the solution only needs to be better than weight times the optimal
+    fn is_useful(&self, g: f64, h: f64) -> bool {
+        let lower_bound = match self.mode {
+            Mode::Normal => g + h,
+            Mode::Greedy => return !self.stop.load(Ordering::Relaxed),
+            Mode::Uniform => g,
+            Mode::Weighted(weight) => weight * (g + h),
+        };
+        lower_bound < self.incumbent()
+    }
+}
+
+// Nodes owned by a single thread
+struct Partition {
+    open_set: BinaryHeap<NodeWithCost>,
+    // gScore and h(n)
+    best_cost_to_node: HashMap<Board, (f64, f64)>,
+    best_path_to_node: HashMap<Board, Board>,
+    expanded: usize,
+    biggest_state: usize,
+}
+
+impl Partition {
+    fn receive(&mut self, shared: &Shared, message: Message) {
+        if let Some((g, _)) = self.best_cost_to_node.get(&message.node) {
+            if message.g >= *g {
+                return;
+            }
+        }
+        // A node found again with a better cost is opened again, even if it was already expanded
+        self.best_cost_to_node
+            .insert(message.node.clone(), (message.g, message.h));
+        self.best_path_to_node
+            .insert(message.node.clone(), message.parent);
+        self.open_set.push(NodeWithCost {
+            cost: shared.mode.cost(message.g, message.h),
+            node: message.node,
+        });
+        if self.open_set.len() > self.biggest_state {
+            self.biggest_state = self.open_set.len();
+        }
+    }
+
+    // Remove outdated entries and return true if the best node of the open set can improve the solution
+    fn has_work(&mut self, shared: &Shared) -> bool {
+        while let Some(current) = self.open_set.peek() {
+            let (g, h) = self.best_cost_to_node[&current.node];
+            if current.cost != shared.mode.cost(g, h) {
+                self.open_set.pop();
+                continue;
+            }
+            return shared.is_useful(g, h);
+        }
+        false
+    }
+}
+
+fn work(
+    shared: &Shared,
+    index: usize,
+    receiver: Receiver<Message>,
+    senders: Vec<Sender<Message>>,
+) -> Partition {
+    let mut partition = Partition {
+        open_set: BinaryHeap::new(),
+        best_cost_to_node: HashMap::new(),
+        best_path_to_node: HashMap::new(),
+        expanded: 0,
+        biggest_state: 0,
+    };
+    let mut idle = false;
+    loop {
+        // A message received while idle takes its place in the work counter
+        while let Ok(message) = receiver.try_recv() {
+            if idle {
+                idle = false;
+            } else {
+                shared.work.fetch_sub(1, Ordering::SeqCst);
+            }
+            partition.receive(shared, message);
+        }
+        if shared.stop.load(Ordering::Relaxed) {
+            break;
+        }
+        if !partition.has_work(shared) {
+            if !idle {
+                idle = true;
+                shared.work.fetch_sub(1, Ordering::SeqCst);
+            }
+            if shared.work.load(Ordering::SeqCst) == 0 {
+                break;
+            }
+            if let Ok(message) = receiver.recv_timeout(Duration::from_millis(1)) {
+                idle = false;
+                partition.receive(shared, message);
+            }
+            continue;
+        }
+
+        let current = partition.open_set.pop().unwrap();
+        let (g, h) = partition.best_cost_to_node[&current.node];
+        partition.expanded += 1;
+        if current.node == *shared.goal {
+            shared.improve_incumbent(g);
+            if matches!(shared.mode, Mode::Greedy) {
+                shared.stop.store(true, Ordering::Relaxed);
+            }
+            continue;
+        }
+        let size = shared.puzzle.size;
+        for neighbor in neighbors(size, &current.node).into_iter().flatten() {
+            let neighbor_h =
+                shared
+                    .heuristic
+                    .update(size, h, &current.node, &neighbor, shared.goal);
+            if !shared.is_useful(g + 1., neighbor_h) {
+                continue;
+            }
+            let owner = shared.owner(&neighbor);
+            let message = Message {
+                node: neighbor,
+                parent: current.node.clone(),
+                g: g + 1.,
+                h: neighbor_h,
+            };
+            if owner == index {
+                partition.receive(shared, message);
+            } else {
+                shared.work.fetch_add(1, Ordering::SeqCst);
+                // The owner may already have stopped and dropped its receiver,
+                // -- the message is only needed while the search goes on
+                let _ = senders[owner].send(message);
+            }
+        }
+    }
+    partition
+}
+
+// Hash distributed A*, each thread owns the nodes with the same hash and expands them,
+// -- generated nodes are sent to the thread that owns them
+pub fn solve(puzzle: &Puzzle, config: &Config, heuristic: Heuristic) -> Result<Solution, String> {
+    let mode = config.mode();
+    let map = Board::from(&puzzle.map[..]);
+    let goal = Board::from(&puzzle.goal[..]);
+    let threads = config.threads;
+    let shared = Shared {
+        puzzle,
+        goal: &goal,
+        heuristic,
+        mode: &mode,
+        threads,
+        incumbent: AtomicU64::new(f64::INFINITY.to_bits()),
+        work: AtomicUsize::new(threads),
+        stop: AtomicBool::new(false),
+    };
+
+    let (senders, receivers): (Vec<Sender<Message>>, Vec<Receiver<Message>>) =
+        (0..threads).map(|_| mpsc::channel()).unzip();
+    let h = heuristic.evaluate(puzzle.size, &map, &goal);
+    let root = Message {
+        node: map.clone(),
+        parent: map.clone(),
+        g: 0.,
+        h,
+    };
+    shared.work.fetch_add(1, Ordering::SeqCst);
+    senders[shared.owner(&map)].send(root).unwrap();
+
+    let partitions: Vec<Partition> = thread::scope(|scope| {
+        let workers: Vec<_> = receivers
+            .into_iter()
+            .enumerate()
+            .map(|(index, receiver)| {
+                let senders = senders.clone();
+                let shared = &shared;
+                scope.spawn(move || work(shared, index, receiver, senders))
+            })
+            .collect();
+        workers
+            .into_iter()
+            .map(|worker| worker.join().unwrap())
+            .collect()
+    });
+
+    if shared.incumbent() == f64::INFINITY {
+        return Err(String::from("Failed to find a solution for this puzzle"));
+    }
+    // Each parent is kept by the owner of the node
+    let mut steps = vec![goal.to_vec()];
+    let mut current = &goal;
+    while *current != map {
+        current = &partitions[shared.owner(current)].best_path_to_node[current];
+        steps.push(current.to_vec());
+    }
+    steps.reverse();
+    Ok(Solution {
+        total_used_states: partitions.iter().map(|partition| partition.expanded).sum(),
+        biggest_state: partitions
+            .iter()
+            .map(|partition| partition.biggest_state)
+            .sum(),
+        moves: blank_moves(puzzle.size, &steps)?,
+        steps,
+        expanded_by_direction: None,
+        suboptimality_bound: mode.suboptimality_bound(&heuristic),
+        transposition_hits: None,
+        layer_histogram: None,
+    })
+}
+
+#[test]
+fn finds_optimal_length() {
+    for threads in [1, 3] {
+        let config = Config {
+            threads,
+            ..Config::default()
+        };
+        crate::puzzle::check_solutions(1, |puzzle| {
+            solve(puzzle, &config, crate::heuristic::MANHATTAN).unwrap()
+        });
+    }
+}
+
+#[test]
+fn greedy_stops_every_thread() {
+    use rand::rngs::StdRng;
+    use rand::SeedableRng;
+    let config = Config {
+        threads: 8,
+        mode: "greedy".to_string(),
+        ..Config::default()
+    };
+    // Workers still sending to the threads that already stopped used to panic
+    for seed in 0..40 {
+        let mut rng = StdRng::seed_from_u64(seed);
+        let puzzle = Puzzle::generate(true, 3, 3, "snail", &mut rng).unwrap();
+        let solution = solve(&puzzle, &config, crate::heuristic::MANHATTAN).unwrap();
+        assert_eq!(solution.steps[0], puzzle.map, "seed {}", seed);
+        assert_eq!(
+            *solution.steps.last().unwrap(),
+            puzzle.goal,
+            "seed {}",
+            seed
+        );
+    }
+}
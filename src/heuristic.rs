@@ -1,22 +1,62 @@
+use crate::{pattern_database, walking_distance};
+use npuzzle::{Board, Heuristic};
+use std::cell::RefCell;
+
+thread_local! {
+    // Goals used by the last evaluations of the thread, and the goal position of each of their tiles
+    static GOAL_POSITIONS: RefCell<Vec<(Board, Vec<usize>)>> = const { RefCell::new(Vec::new()) };
+}
+
+// Call f with the goal position of each tile, indexed by tile
+fn with_goal_positions<T>(goal: &Board, f: impl FnOnce(&[usize]) -> T) -> T {
+    GOAL_POSITIONS.with(|cache| {
+        let mut cache = cache.borrow_mut();
+        if let Some((_, positions)) = cache.iter().find(|(cached, _)| cached == goal) {
+            return f(positions);
+        }
+        let mut positions = vec![0; goal.iter().max().unwrap_or(0) as usize + 1];
+        for (index, cell) in goal.iter().enumerate() {
+            positions[cell as usize] = index;
+        }
+        // Bidirectional searches alternate between two goals
+        if cache.len() >= 2 {
+            cache.remove(0);
+        }
+        cache.push((goal.clone(), positions));
+        f(&cache.last().unwrap().1)
+    })
+}
+
+// The tile that moved between a parent and its child, and its position in the parent and in the child
+fn moved_tile(parent: &Board, child: &Board) -> (i32, usize, usize) {
+    (child.get(parent.blank()), child.blank(), parent.blank())
+}
+
 // Number of different cells between two Nodes
-pub fn hamming(_: i32, node: &[i32], goal: &[i32]) -> f64 {
+pub fn hamming(_: i32, node: &Board, goal: &Board) -> f64 {
     node.iter()
-        .zip(goal)
-        .map(|(x, y)| if *x > 0 && x != y { 1. } else { 0. })
+        .zip(goal.iter())
+        .map(|(x, y)| if x > 0 && x != y { 1. } else { 0. })
         .sum()
 }
 
+pub fn hamming_update(_: i32, value: f64, parent: &Board, child: &Board, goal: &Board) -> f64 {
+    let (tile, from, to) = moved_tile(parent, child);
+    let misplaced = |index: usize| if goal.get(index) == tile { 0. } else { 1. };
+    value - misplaced(from) + misplaced(to)
+}
+
 #[test]
 fn hamming_one() {
-    let left = vec![1, 2, 3, 4, 5, 6, 7, 8, 0];
-    let right = vec![1, 2, 3, 4, 5, 6, 7, 0, 8];
+    let left = Board::from(vec![1, 2, 3, 4, 5, 6, 7, 8, 0]);
+    let right = Board::from(vec![1, 2, 3, 4, 5, 6, 7, 0, 8]);
     assert_eq!(hamming(3, &left, &right), 1.)
 }
 
 #[test]
 fn hamming_two() {
-    let left = vec![18, 8, 7, 4, 9, 6, 12, 24, 11];
-    let right = vec![12, 24, 18, 8, 7, 4, 11, 9, 6];
+    let left = Board::from(vec![18, 8, 7, 4, 9, 6, 12, 24, 11]);
+    let right = Board::from(vec![12, 24, 18, 8, 7, 4, 11, 9, 6]);
     assert_eq!(hamming(3, &left, &right), 9.)
 }
 
@@ -29,185 +69,344 @@ pub fn manhattan_distance(size: i32, index: usize, goal: usize) -> f64 {
 
 // Sum of the manhattan distance for each cell in the Node
 // sum(abs(x - y))
-pub fn manhattan(size: i32, node: &[i32], goal: &[i32]) -> f64 {
-    node.iter()
-        .zip(goal)
-        .enumerate()
-        .map(|(index, (&x, &y))| {
-            if x > 0 && x != y {
-                let goal_index = goal.iter().position(|&to_find| x == to_find).unwrap();
-                manhattan_distance(size, index, goal_index)
-            } else {
-                0.
-            }
-        })
-        .sum::<f64>()
+pub fn manhattan(size: i32, node: &Board, goal: &Board) -> f64 {
+    with_goal_positions(goal, |positions| {
+        node.iter()
+            .enumerate()
+            .map(|(index, x)| {
+                if x > 0 {
+                    manhattan_distance(size, index, positions[x as usize])
+                } else {
+                    0.
+                }
+            })
+            .sum::<f64>()
+    })
 }
 
+// Only the distance of the moved tile changes
+pub fn manhattan_update(size: i32, value: f64, parent: &Board, child: &Board, goal: &Board) -> f64 {
+    let (tile, from, to) = moved_tile(parent, child);
+    with_goal_positions(goal, |positions| {
+        let goal_index = positions[tile as usize];
+        value - manhattan_distance(size, from, goal_index)
+            + manhattan_distance(size, to, goal_index)
+    })
+}
+
+// Default heuristic
+pub const MANHATTAN: Heuristic = Heuristic::incremental(manhattan, manhattan_update);
+
 #[test]
 fn manhattan_one() {
-    let left = vec![1, 2, 3, 4, 5, 6, 7, 8, 0];
-    let right = vec![1, 2, 3, 4, 5, 6, 7, 0, 8];
+    let left = Board::from(vec![1, 2, 3, 4, 5, 6, 7, 8, 0]);
+    let right = Board::from(vec![1, 2, 3, 4, 5, 6, 7, 0, 8]);
     assert_eq!(manhattan(3, &left, &right), 1.)
 }
 
 #[test]
 fn manhattan_two() {
-    let left = vec![18, 8, 7, 4, 9, 6, 12, 24, 11];
-    let right = vec![12, 24, 18, 8, 7, 4, 11, 9, 6];
+    let left = Board::from(vec![18, 8, 7, 4, 9, 6, 12, 24, 11]);
+    let right = Board::from(vec![12, 24, 18, 8, 7, 4, 11, 9, 6]);
     assert_eq!(manhattan(3, &left, &right), 16.)
 }
 
 #[test]
 fn manhattan_three() {
-    let left = vec![7, 2, 4, 5, 0, 6, 8, 3, 1];
-    let right = vec![1, 2, 3, 8, 0, 4, 7, 6, 5];
+    let left = Board::from(vec![7, 2, 4, 5, 0, 6, 8, 3, 1]);
+    let right = Board::from(vec![1, 2, 3, 8, 0, 4, 7, 6, 5]);
     assert_eq!(manhattan(3, &left, &right), 16.)
 }
 
+fn squared_distance(size: i32, index: usize, goal: usize) -> f64 {
+    let size = size as usize;
+    let (x_n, y_n) = ((index % size) as i32, (index / size) as i32);
+    let (x_g, y_g) = ((goal % size) as i32, (goal / size) as i32);
+    ((x_n - x_g) as f64).powf(2.) + ((y_n - y_g) as f64).powf(2.)
+}
+
 // Sum of the euclidean distance for each cell in the Node
 // sqrt(sum((x - y) ** 2))
-pub fn euclidean_distance(size: i32, node: &[i32], goal: &[i32]) -> f64 {
-    node.iter()
-        .zip(goal)
-        .enumerate()
-        .map(|(index, (&x, &y))| {
-            if x > 0 && x != y {
-                let size = size as usize;
-                let goal_index = goal.iter().position(|&to_find| x == to_find).unwrap();
-                let (x_n, y_n) = ((index % size) as i32, (index / size) as i32);
-                let (x_g, y_g) = ((goal_index % size) as i32, (goal_index / size) as i32);
-                ((x_n - x_g) as f64).powf(2.) + ((y_n - y_g) as f64).powf(2.)
-            } else {
-                0.
-            }
-        })
-        .sum::<f64>()
+pub fn euclidean_distance(size: i32, node: &Board, goal: &Board) -> f64 {
+    with_goal_positions(goal, |positions| {
+        node.iter()
+            .enumerate()
+            .map(|(index, x)| {
+                if x > 0 {
+                    squared_distance(size, index, positions[x as usize])
+                } else {
+                    0.
+                }
+            })
+            .sum::<f64>()
+            .sqrt()
+    })
+}
+
+// The sum of squares is an integer, so it can be found back from the square root
+pub fn euclidean_distance_update(
+    size: i32,
+    value: f64,
+    parent: &Board,
+    child: &Board,
+    goal: &Board,
+) -> f64 {
+    let (tile, from, to) = moved_tile(parent, child);
+    with_goal_positions(goal, |positions| {
+        let goal_index = positions[tile as usize];
+        ((value * value).round() - squared_distance(size, from, goal_index)
+            + squared_distance(size, to, goal_index))
         .sqrt()
+    })
 }
 
 #[test]
 fn euclidean_distance_one() {
-    let left = vec![1, 2, 3, 4, 5, 6, 7, 8, 0];
-    let right = vec![1, 2, 3, 4, 5, 6, 7, 0, 8];
+    let left = Board::from(vec![1, 2, 3, 4, 5, 6, 7, 8, 0]);
+    let right = Board::from(vec![1, 2, 3, 4, 5, 6, 7, 0, 8]);
     assert_eq!(euclidean_distance(3, &left, &right), 1.)
 }
 
 #[test]
 fn euclidean_distance_two() {
-    let left = vec![18, 8, 7, 4, 9, 6, 12, 24, 11];
-    let right = vec![12, 24, 18, 8, 7, 4, 11, 9, 6];
+    let left = Board::from(vec![18, 8, 7, 4, 9, 6, 12, 24, 11]);
+    let right = Board::from(vec![12, 24, 18, 8, 7, 4, 11, 9, 6]);
     assert_eq!(euclidean_distance(3, &left, &right), 5.0990195135927845)
 }
 
-struct LinearConflictCell {
-    goal: usize,
-    goal_line: usize,
-    goal_column: usize,
+// Fewest tiles of a line that must leave it so that the tiles left are in the order of their goal,
+// -- only the tiles whose goal is in this line count, and each leaving tile costs two more moves
+fn line_conflicts(
+    size: usize,
+    node: &Board,
+    positions: &[usize],
     line: usize,
-    column: usize,
+    vertical: bool,
+) -> f64 {
+    let goal_line = |goal: usize| {
+        if vertical {
+            goal % size
+        } else {
+            goal / size
+        }
+    };
+    // Columns have as many cells as there are rows
+    let (first, step, length) = if vertical {
+        (line, size, node.len() / size)
+    } else {
+        (line * size, 1, size)
+    };
+    // Smallest last goal of the sequences of tiles in order of each length,
+    // -- kept on the stack for the usual sizes since this runs for each move
+    let mut buffer = [0; 16];
+    let mut heap = Vec::new();
+    let tails: &mut [usize] = if length <= buffer.len() {
+        &mut buffer
+    } else {
+        heap.resize(length, 0);
+        &mut heap
+    };
+    let (mut tiles, mut in_order) = (0, 0);
+    for offset in 0..length {
+        let tile = node.get(first + offset * step);
+        if tile == 0 || goal_line(positions[tile as usize]) != line {
+            continue;
+        }
+        let goal = positions[tile as usize];
+        let sequence = tails[..in_order].partition_point(|&tail| tail < goal);
+        tails[sequence] = goal;
+        if sequence == in_order {
+            in_order += 1;
+        }
+        tiles += 1;
+    }
+    (tiles - in_order) as f64
 }
 
-// Sum of the manhattan distance + linear conflicts for each cell in the Node
+// Sum of the manhattan distance + linear conflicts of each row and column of the Node
 // sum(abs(x - y)) + 2*linear_conflicts
-pub fn linear_conflicts(size: i32, node: &[i32], goal: &[i32]) -> f64 {
+pub fn linear_conflicts(size: i32, node: &Board, goal: &Board) -> f64 {
     let manhattan_distance = manhattan(size, node, goal);
     let size: usize = size.try_into().unwrap();
-    let mut linear_conflicts = 0.;
-
-    // Build goal map to avoid recalculation
-    let cell_state: Vec<LinearConflictCell> = (0..(size * size))
-        .map(|index| {
-            let cell_value = node[index];
-            let goal = goal.iter().position(|&v| cell_value == v).unwrap();
-            LinearConflictCell {
-                goal,
-                goal_line: goal / size,
-                goal_column: goal % size,
-                line: index / size,
-                column: index % size,
-            }
-        })
-        .collect();
+    let linear_conflicts: f64 = with_goal_positions(goal, |positions| {
+        (0..node.len() / size)
+            .map(|row| line_conflicts(size, node, positions, row, false))
+            .chain((0..size).map(|column| line_conflicts(size, node, positions, column, true)))
+            .sum()
+    });
+    manhattan_distance + (linear_conflicts * 2.)
+}
 
-    // j is the node being checked and k is the next in row/column
-    for (index, &j) in node.iter().enumerate() {
-        // Ignore empty cell
-        if j == 0 {
-            continue;
-        }
-        // Calculate j shared state between rows and columns
-        let current_cell = &cell_state[index];
-        // Process cells on the same line for horizontal conflicts
-        if current_cell.column > 0 && current_cell.line == current_cell.goal_line {
-            // j is on the right, k is on the left
-            for offset in 1..=current_cell.column {
-                let k = node[index - offset];
-                if k == 0 {
-                    continue;
-                }
-                let k_cell = &cell_state[index - offset];
-                // The goal positions of j and k are on the same lines
-                // -- and j is on the right from k
-                if current_cell.goal_line == k_cell.goal_line && k_cell.goal >= current_cell.goal {
-                    linear_conflicts += 1.;
-                }
-            }
-        }
-        // --  and row for vertical conflicts
-        if current_cell.line > 0 && current_cell.column == current_cell.goal_column {
-            // j is down, k is on top
-            for offset in 1..=current_cell.line {
-                let k = node[index - (offset * size)];
-                if k == 0 {
-                    continue;
-                }
-                let k_cell = &cell_state[index - (offset * size)];
-                // The goal positions of j and k are on the same columns
-                // -- and j is down from k
-                if current_cell.goal_column == k_cell.goal_column
-                    && k_cell.goal >= current_cell.goal
-                {
-                    linear_conflicts += 1.;
-                }
+pub fn linear_conflicts_update(
+    size: i32,
+    value: f64,
+    parent: &Board,
+    child: &Board,
+    goal: &Board,
+) -> f64 {
+    let (tile, from, to) = moved_tile(parent, child);
+    // A tile sliding along a row keeps the order of its row and only changes the columns it leaves
+    // -- and enters, and the opposite for a tile sliding along a column
+    let vertical = from.abs_diff(to) == 1;
+    with_goal_positions(goal, |positions| {
+        let goal_index = positions[tile as usize];
+        let lines = size as usize;
+        let line = |position: usize| {
+            if vertical {
+                position % lines
+            } else {
+                position / lines
             }
-        }
-    }
-
-    manhattan_distance + (linear_conflicts * 2.)
+        };
+        let conflicts = |node: &Board| {
+            line_conflicts(lines, node, positions, line(from), vertical)
+                + line_conflicts(lines, node, positions, line(to), vertical)
+        };
+        value - manhattan_distance(size, from, goal_index)
+            + manhattan_distance(size, to, goal_index)
+            + 2. * (conflicts(child) - conflicts(parent))
+    })
 }
 
 #[test]
 fn linear_conflicts_one() {
-    let left = vec![1, 2, 3, 4, 5, 6, 7, 8, 0];
-    let right = vec![1, 2, 3, 4, 5, 6, 7, 0, 8];
+    let left = Board::from(vec![1, 2, 3, 4, 5, 6, 7, 8, 0]);
+    let right = Board::from(vec![1, 2, 3, 4, 5, 6, 7, 0, 8]);
     assert_eq!(linear_conflicts(3, &left, &right), 1.)
 }
 
 #[test]
 fn linear_conflicts_two() {
-    let left = vec![18, 8, 7, 4, 9, 6, 12, 24, 11];
-    let right = vec![12, 24, 18, 8, 7, 4, 11, 9, 6];
+    let left = Board::from(vec![18, 8, 7, 4, 9, 6, 12, 24, 11]);
+    let right = Board::from(vec![12, 24, 18, 8, 7, 4, 11, 9, 6]);
     assert_eq!(linear_conflicts(3, &left, &right), 18.)
 }
 
 #[test]
 fn linear_conflicts_three() {
-    let left = vec![2, 1, 3, 8, 0, 4, 7, 6, 5];
-    let right = vec![1, 2, 3, 8, 0, 4, 7, 6, 5];
+    let left = Board::from(vec![2, 1, 3, 8, 0, 4, 7, 6, 5]);
+    let right = Board::from(vec![1, 2, 3, 8, 0, 4, 7, 6, 5]);
     assert_eq!(linear_conflicts(3, &left, &right), 4.)
 }
 
 #[test]
 fn linear_conflicts_four() {
-    let left = vec![8, 2, 3, 1, 0, 4, 7, 6, 5];
-    let right = vec![1, 2, 3, 8, 0, 4, 7, 6, 5];
+    let left = Board::from(vec![8, 2, 3, 1, 0, 4, 7, 6, 5]);
+    let right = Board::from(vec![1, 2, 3, 8, 0, 4, 7, 6, 5]);
     assert_eq!(linear_conflicts(3, &left, &right), 4.)
 }
 
 #[test]
 fn linear_conflicts_five() {
-    let left = vec![4, 2, 5, 1, 0, 6, 3, 8, 7];
-    let right = vec![1, 2, 3, 4, 5, 6, 7, 8, 0];
+    let left = Board::from(vec![4, 2, 5, 1, 0, 6, 3, 8, 7]);
+    let right = Board::from(vec![1, 2, 3, 4, 5, 6, 7, 8, 0]);
     assert_eq!(linear_conflicts(3, &left, &right), 14.)
 }
+
+// Sum of the exact cost of each disjoint pattern of tiles, precomputed for the goal
+pub fn pattern_database(size: i32, node: &Board, goal: &Board) -> f64 {
+    pattern_database::get(size, goal).evaluate(node) as f64
+}
+
+#[test]
+fn pattern_database_one() {
+    let left = Board::from(vec![1, 2, 3, 4, 5, 6, 7, 8, 0]);
+    let right = Board::from(vec![1, 2, 3, 4, 5, 6, 7, 0, 8]);
+    assert_eq!(pattern_database(3, &left, &right), 1.)
+}
+
+#[test]
+fn pattern_database_two() {
+    let left = Board::from(vec![2, 1, 3, 8, 0, 4, 7, 6, 5]);
+    let right = Board::from(vec![1, 2, 3, 8, 0, 4, 7, 6, 5]);
+    assert_eq!(pattern_database(3, &left, &right), 8.)
+}
+
+#[test]
+fn pattern_database_three() {
+    let left = Board::from(vec![7, 2, 4, 5, 0, 6, 8, 3, 1]);
+    let right = Board::from(vec![1, 2, 3, 8, 0, 4, 7, 6, 5]);
+    assert!(pattern_database(3, &left, &right) >= manhattan(3, &left, &right))
+}
+
+// Sum of the moves needed between rows and between columns, precomputed for each goal line of the empty cell
+pub fn walking_distance(size: i32, node: &Board, goal: &Board) -> f64 {
+    walking_distance::evaluate(size, node, goal) as f64
+}
+
+#[test]
+fn walking_distance_one() {
+    let left = Board::from(vec![1, 2, 3, 4, 5, 6, 7, 8, 0]);
+    let right = Board::from(vec![1, 2, 3, 4, 5, 6, 7, 0, 8]);
+    assert_eq!(walking_distance(3, &left, &right), 1.)
+}
+
+#[test]
+fn walking_distance_two() {
+    let left = Board::from(vec![1, 2, 3, 4, 5, 6, 7, 8, 9, 10, 11, 12, 0, 15, 13, 14]);
+    let right = Board::from(vec![1, 2, 3, 4, 5, 6, 7, 8, 9, 10, 11, 12, 13, 14, 15, 0]);
+    assert_eq!(walking_distance(4, &left, &right), 9.)
+}
+
+#[test]
+fn walking_distance_maps() {
+    use crate::puzzle::Puzzle;
+    // Optimal solution length of each solvable map, if it is known
+    let maps = [
+        ("puzzles/solv_3_1.map", "snail", Some(26.)),
+        ("puzzles/solv_3_1_first.map", "first", Some(1.)),
+        ("puzzles/solv_3_1_last.map", "last", Some(3.)),
+        ("puzzles/solv_3_2.map", "snail", Some(1.)),
+        ("puzzles/solv_3_2_last.map", "last", Some(24.)),
+        ("puzzles/solv_3_3.map", "snail", Some(20.)),
+        ("puzzles/solv_3_4.map", "snail", Some(30.)),
+        ("puzzles/solv_4_1.map", "snail", Some(48.)),
+        ("puzzles/solv_4_1_last.map", "last", None),
+        ("puzzles/solv_4_2.map", "snail", Some(1.)),
+    ];
+    for (path, solution_type, optimal) in maps {
+        let puzzle = Puzzle::new(path, solution_type).unwrap();
+        let (map, goal) = (Board::from(puzzle.map), Board::from(puzzle.goal));
+        let distance = walking_distance(puzzle.size, &map, &goal);
+        assert!(distance >= manhattan(puzzle.size, &map, &goal));
+        if let Some(optimal) = optimal {
+            assert!(distance <= optimal, "{} is not admissible", path);
+        }
+    }
+}
+
+#[test]
+fn incremental_matches_full() {
+    use npuzzle::neighbors;
+    use rand::prelude::SliceRandom;
+    use rand::rngs::StdRng;
+    use rand::SeedableRng;
+    let heuristics = [
+        (
+            hamming as npuzzle::HeuristicFn,
+            hamming_update as npuzzle::UpdateFn,
+        ),
+        (manhattan, manhattan_update),
+        (euclidean_distance, euclidean_distance_update),
+        (linear_conflicts, linear_conflicts_update),
+    ];
+    for goal in [
+        vec![1, 2, 3, 8, 0, 4, 7, 6, 5],
+        vec![1, 2, 3, 4, 12, 13, 14, 5, 11, 0, 15, 6, 10, 9, 8, 7],
+    ] {
+        let size = (goal.len() as f64).sqrt() as i32;
+        let goal = Board::from(goal);
+        let mut rng = StdRng::seed_from_u64(42);
+        for (full, update) in heuristics {
+            let mut node = goal.clone();
+            let mut value = full(size, &node, &goal);
+            for _ in 0..500 {
+                let next: Vec<Board> = neighbors(size, &node).into_iter().flatten().collect();
+                let child = next.choose(&mut rng).unwrap().clone();
+                value = update(size, value, &node, &child, &goal);
+                assert!((value - full(size, &child, &goal)).abs() < 1e-9);
+                node = child;
+            }
+        }
+    }
+}
@@ -0,0 +1,149 @@
+use crate::lookup_table;
+use npuzzle::{neighbors, Board, Heuristic};
+use std::collections::{HashMap, VecDeque};
+
+// Distance of the boards enumerated around the goal for puzzles other than 3x3
+pub const DEFAULT_MAX_DISTANCE: usize = 14;
+
+// Heuristics are compared with float costs, which can be slightly off for the euclidean distance
+const EPSILON: f64 = 1e-9;
+
+// Every board with its exact distance to the goal,
+// -- every solvable board for 3x3 or else every board up to max_distance moves from the goal
+pub fn exact_distances(size: i32, goal: &Board, max_distance: usize) -> Vec<(Board, usize)> {
+    if size == lookup_table::SIZE && goal.len() == lookup_table::CELLS {
+        return lookup_table::get(goal)
+            .states()
+            .map(|(board, distance)| (board, distance as usize))
+            .collect();
+    }
+
+    let mut distances = HashMap::new();
+    distances.insert(goal.clone(), 0);
+    let mut queue = VecDeque::from([goal.clone()]);
+    while let Some(current) = queue.pop_front() {
+        let distance = distances[&current];
+        if distance == max_distance {
+            continue;
+        }
+        for neighbor in neighbors(size, &current).into_iter().flatten() {
+            if !distances.contains_key(&neighbor) {
+                distances.insert(neighbor.clone(), distance + 1);
+                queue.push_back(neighbor);
+            }
+        }
+    }
+    distances.into_iter().collect()
+}
+
+#[derive(Default)]
+pub struct Report {
+    pub boards: usize,
+    // Boards where the heuristic is above the exact distance
+    pub admissibility_violations: usize,
+    // Biggest difference between the heuristic and the exact distance, with its board
+    pub worst_overestimate: Option<(f64, Board)>,
+    pub moves: usize,
+    // Moves where the heuristic decreases by more than the cost of the move
+    pub consistency_violations: usize,
+    // Sum of the heuristic divided by the exact distance, for boards other than the goal
+    accuracy: f64,
+    accuracy_boards: usize,
+}
+
+impl Report {
+    // Average of the heuristic divided by the exact distance, 1 is a perfect heuristic
+    pub fn accuracy(&self) -> f64 {
+        if self.accuracy_boards == 0 {
+            1.
+        } else {
+            self.accuracy / self.accuracy_boards as f64
+        }
+    }
+}
+
+// Compare the heuristic to the exact distance of each board and to the heuristic of its neighbors
+pub fn check(size: i32, goal: &Board, heuristic: Heuristic, boards: &[(Board, usize)]) -> Report {
+    let mut report = Report::default();
+    for (board, distance) in boards {
+        let h = heuristic.evaluate(size, board, goal);
+        let distance = *distance as f64;
+        report.boards += 1;
+        if h > distance + EPSILON {
+            report.admissibility_violations += 1;
+            if report
+                .worst_overestimate
+                .as_ref()
+                .is_none_or(|(worst, _)| h - distance > *worst)
+            {
+                report.worst_overestimate = Some((h - distance, board.clone()));
+            }
+        }
+        if distance > 0. {
+            report.accuracy += h / distance;
+            report.accuracy_boards += 1;
+        }
+        for neighbor in neighbors(size, board).into_iter().flatten() {
+            report.moves += 1;
+            if h > heuristic.evaluate(size, &neighbor, goal) + 1. + EPSILON {
+                report.consistency_violations += 1;
+            }
+        }
+    }
+    report
+}
+
+#[test]
+fn admissible_heuristics() {
+    use crate::heuristic;
+    let goal = Board::from(vec![1, 2, 3, 8, 0, 4, 7, 6, 5]);
+    let boards = exact_distances(3, &goal, DEFAULT_MAX_DISTANCE);
+    assert_eq!(boards.len(), 181_440);
+    for heuristic in [
+        heuristic::manhattan as npuzzle::HeuristicFn,
+        heuristic::euclidean_distance,
+        heuristic::linear_conflicts,
+    ] {
+        let report = check(3, &goal, Heuristic::new(heuristic), &boards);
+        assert_eq!(report.admissibility_violations, 0);
+        assert!(report.accuracy() > 0. && report.accuracy() <= 1.);
+    }
+    for heuristic in [heuristic::manhattan, heuristic::linear_conflicts] {
+        let report = check(3, &goal, Heuristic::new(heuristic), &boards);
+        assert_eq!(report.consistency_violations, 0);
+    }
+}
+
+#[test]
+fn overestimating_heuristic() {
+    fn twice_manhattan(size: i32, node: &Board, goal: &Board) -> f64 {
+        2. * crate::heuristic::manhattan(size, node, goal)
+    }
+    let goal = Board::from(vec![1, 2, 3, 4, 5, 6, 7, 8, 9, 10, 11, 12, 13, 14, 15, 0]);
+    let boards = exact_distances(4, &goal, 6);
+    assert!(boards.iter().all(|(_, distance)| *distance <= 6));
+    let report = check(4, &goal, Heuristic::new(twice_manhattan), &boards);
+    assert_eq!(report.boards, boards.len());
+    assert!(report.admissibility_violations > 0);
+    assert_eq!(report.worst_overestimate.unwrap().0, 6.);
+    assert!(report.consistency_violations > 0);
+}
+
+#[test]
+fn custom_goal() {
+    use crate::heuristic;
+    let (size, _, goal) = crate::goal::load("puzzles/goal_3_1.map").unwrap();
+    let goal = Board::from(goal);
+    let boards = exact_distances(size, &goal, DEFAULT_MAX_DISTANCE);
+    assert_eq!(boards.len(), 181_440);
+    for heuristic in [
+        heuristic::manhattan as npuzzle::HeuristicFn,
+        heuristic::euclidean_distance,
+        heuristic::linear_conflicts,
+        heuristic::pattern_database,
+        heuristic::walking_distance,
+    ] {
+        let report = check(size, &goal, Heuristic::new(heuristic), &boards);
+        assert_eq!(report.admissibility_violations, 0);
+    }
+}
@@ -1,146 +1,513 @@
+use crate::config::Config;
+use crate::move_pruning::{direction, MovePruning};
 use crate::puzzle::Puzzle;
-use npuzzle::{neighbors, HeuristicFn, Mode, Solution};
+use crate::transposition_table::TranspositionTable;
+use npuzzle::{blank_moves, neighbors, Board, Heuristic, Mode, Solution};
+use std::collections::hash_map::DefaultHasher;
+use std::collections::HashSet;
+use std::hash::{Hash, Hasher};
+use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+use std::sync::Mutex;
+use std::thread;
 use std::time::Instant;
 
+// Amount of work items generated for each thread, so threads that finish early can take more
+const WORK_PER_THREAD: usize = 16;
+
+#[derive(Default)]
 pub struct Summary {
     total_used_states: usize,
     biggest_state: usize,
+    transposition_hits: usize,
+    transposition_misses: usize,
+}
+
+impl Summary {
+    fn add(&mut self, other: &Summary) {
+        self.total_used_states += other.total_used_states;
+        self.transposition_hits += other.transposition_hits;
+        self.transposition_misses += other.transposition_misses;
+    }
+}
+
+// Everything that doesn't change during the search
+pub struct Search<'a> {
+    puzzle: &'a Puzzle,
+    goal: &'a Board,
+    heuristic: Heuristic,
+    mode: &'a Mode,
+    pruning: &'a MovePruning,
+    // Set when another thread found the goal
+    stop: &'a AtomicBool,
+    table: Option<&'a TranspositionTable>,
+}
+
+impl Search<'_> {
+    // Check if the node was already reached at a lower depth in this iteration,
+    // -- with the finite state machine the moves that are allowed also depend on its state
+    fn is_transposition(
+        &self,
+        summary: &mut Summary,
+        node: &Board,
+        depth: f64,
+        moves: usize,
+    ) -> bool {
+        let table = match self.table {
+            Some(table) => table,
+            None => return false,
+        };
+        let mut hasher = DefaultHasher::new();
+        node.hash(&mut hasher);
+        if !self.pruning.is_inverse() {
+            moves.hash(&mut hasher);
+        }
+        if table.probe(hasher.finish(), depth as usize) {
+            summary.transposition_hits += 1;
+            true
+        } else {
+            summary.transposition_misses += 1;
+            false
+        }
+    }
 }
 
-pub struct Branch<'a> {
-    path: &'a mut Vec<Vec<i32>>,
+// Node of the current path, with the neighbors that are left to check
+pub struct Frame {
+    node: Board,
     depth: f64,
-    bound: f64,
+    // Heuristic value of the node
+    h: f64,
+    // Neighbors sorted by their heuristic value, with the automaton state after their move
+    neighbors: [Option<(f64, Board, usize)>; 4],
+    next: usize,
+    // Lowest score above the bound found under this node
+    min: f64,
+}
+
+impl Frame {
+    // moves is the state of the move pruning automaton after the moves of the path to the node
+    fn new(search: &Search, node: Board, depth: f64, h: f64, moves: usize) -> Frame {
+        // Sort each neighbors by their heuristic value, and ignore redundant moves
+        let size = search.puzzle.size;
+        let mut neighbors = neighbors(size, &node).map(|neighbor| {
+            let neighbor = neighbor?;
+            let moves = search
+                .pruning
+                .next(moves, direction(size, &node, &neighbor))?;
+            let h = search
+                .heuristic
+                .update(size, h, &node, &neighbor, search.goal);
+            Some((h, neighbor, moves))
+        });
+        neighbors.sort_by(|a, b| {
+            let a = a.as_ref().map_or(f64::INFINITY, |neighbor| neighbor.0);
+            let b = b.as_ref().map_or(f64::INFINITY, |neighbor| neighbor.0);
+            a.partial_cmp(&b).unwrap()
+        });
+        Frame {
+            node,
+            depth,
+            h,
+            neighbors,
+            next: 0,
+            min: f64::INFINITY,
+        }
+    }
 }
 
 pub struct BranchResult {
     score: f64,
-    result: Option<Vec<i32>>,
+    found: bool,
 }
 
-// "unnecessary unwrap" is necessary because two matchs in the same condition make things weird
-#[allow(clippy::unnecessary_unwrap)]
+// Depth-first search of every node under the bound, with an explicit stack instead of recursion,
+// -- the stack contains the path to the goal if it's found
 pub fn evaluate_branch(
-    puzzle: &Puzzle,
+    search: &Search,
     summary: &mut Summary,
-    branch: &mut Branch,
-    heuristic: HeuristicFn,
-    mode: &Mode,
+    stack: &mut Vec<Frame>,
+    bound: f64,
 ) -> BranchResult {
+    let root = stack.last().unwrap();
     summary.total_used_states += 1;
-    let node = branch.path.last().unwrap();
-    // Check if node is withinn bound
-    let f = match mode {
-        Mode::Normal => branch.depth + heuristic(puzzle.size, node, &puzzle.goal),
-        Mode::Greedy => heuristic(puzzle.size, node, &puzzle.goal), // Ignore depth
-        Mode::Uniform => branch.depth,                              // Ignore heuristic
-    };
-    if f > branch.bound {
+    let score = search.mode.cost(root.depth, root.h);
+    if score > bound || root.node == *search.goal {
         return BranchResult {
-            score: f,
-            result: None,
+            score,
+            found: score <= bound,
         };
     }
-    if *node == puzzle.goal {
-        return BranchResult {
-            score: f,
-            result: Some(node.clone()),
-        };
+    // Without a depth limit, greedy mode could go around longer cycles forever
+    let greedy = matches!(search.mode, Mode::Greedy);
+    let mut on_path: HashSet<Board> = HashSet::new();
+    if greedy {
+        on_path.insert(root.node.clone());
     }
-    // Sort each neighbors by their heuristic value
-    let mut neighbors = neighbors(puzzle.size, node).map(|neighbor| {
-        if neighbor.is_some() {
-            let neighbor = neighbor.unwrap();
-            (
-                heuristic(puzzle.size, &neighbor, &puzzle.goal),
-                Some(neighbor),
-            )
-        } else {
-            (f64::INFINITY, None)
+
+    loop {
+        if summary.total_used_states.is_multiple_of(4096) && search.stop.load(Ordering::Relaxed) {
+            return BranchResult {
+                score: f64::INFINITY,
+                found: false,
+            };
         }
-    });
-    neighbors.sort_by(|a, b| a.0.partial_cmp(&b.0).unwrap());
-    // Check each neighors
-    let mut min = f64::INFINITY;
-    for (_, neighbor) in neighbors.into_iter() {
-        if neighbor.is_none() {
-            break;
+        let frame = stack.last_mut().unwrap();
+        if frame.next >= frame.neighbors.len() || frame.neighbors[frame.next].is_none() {
+            // Every neighbor was checked, go back to the parent
+            let frame = stack.pop().unwrap();
+            if greedy {
+                on_path.remove(&frame.node);
+            }
+            match stack.last_mut() {
+                Some(parent) => parent.min = parent.min.min(frame.min),
+                None => {
+                    return BranchResult {
+                        score: frame.min,
+                        found: false,
+                    }
+                }
+            }
+            continue;
+        }
+        let (h, neighbor, moves) = frame.neighbors[frame.next].take().unwrap();
+        frame.next += 1;
+        if greedy && on_path.contains(&neighbor) {
+            continue;
+        }
+
+        summary.total_used_states += 1;
+        let depth = frame.depth + 1.;
+        let score = search.mode.cost(depth, h);
+        if score > bound {
+            frame.min = frame.min.min(score);
+            continue;
         }
-        let neighbor = neighbor.unwrap();
-        if branch.path.contains(&neighbor) {
+        let found = neighbor == *search.goal;
+        if !found && search.is_transposition(summary, &neighbor, depth, moves) {
             continue;
         }
-        branch.path.push(neighbor);
-        if branch.path.len() > summary.biggest_state {
-            summary.biggest_state = branch.path.len();
-        }
-        let branch_result = evaluate_branch(
-            puzzle,
-            summary,
-            &mut Branch {
-                path: branch.path,
-                depth: branch.depth + 1.,
-                bound: branch.bound,
-            },
-            heuristic,
-            mode,
-        );
-        if branch_result.result.is_some() {
-            return branch_result;
-        }
-        if branch_result.score < min {
-            min = branch_result.score
-        }
-        branch.path.pop();
+        if greedy {
+            on_path.insert(neighbor.clone());
+        }
+        stack.push(Frame::new(search, neighbor, depth, h, moves));
+        if stack.len() > summary.biggest_state {
+            summary.biggest_state = stack.len();
+        }
+        if found {
+            return BranchResult { score, found };
+        }
     }
-    BranchResult {
-        score: min,
-        result: None,
+}
+
+// Start of a branch of the search tree, solved by a single thread
+struct WorkItem {
+    path: Vec<Board>,
+    h: f64,
+    moves: usize,
+}
+
+// Search under a single bound and return the next bound, or the path to the goal
+fn iteration(
+    search: &Search,
+    summary: &mut Summary,
+    root: &WorkItem,
+    bound: f64,
+) -> (f64, Option<Vec<Board>>) {
+    let node = root.path.last().unwrap().clone();
+    let depth = (root.path.len() - 1) as f64;
+    let mut stack = vec![Frame::new(search, node, depth, root.h, root.moves)];
+    let result = evaluate_branch(search, summary, &mut stack, bound);
+    if !result.found {
+        return (result.score, None);
     }
+    let mut path = root.path.clone();
+    path.pop();
+    path.extend(stack.into_iter().map(|frame| frame.node));
+    (result.score, Some(path))
 }
 
-pub fn solve(puzzle: &Puzzle, mode: &str, heuristic: HeuristicFn) -> Result<Solution, String> {
+// Expand the first levels of the tree until there is enough work items for each thread,
+// -- the lowest score above the bound is kept in min
+fn split(
+    search: &Search,
+    summary: &mut Summary,
+    root: WorkItem,
+    bound: f64,
+    threads: usize,
+    min: &mut f64,
+) -> Result<Vec<WorkItem>, Vec<Board>> {
+    let greedy = matches!(search.mode, Mode::Greedy);
+    let mut items = vec![root];
+    while !items.is_empty() && items.len() < threads * WORK_PER_THREAD {
+        let mut next_items = vec![];
+        for item in items {
+            let node = item.path.last().unwrap();
+            summary.total_used_states += 1;
+            if search.mode.cost((item.path.len() - 1) as f64, item.h) > bound {
+                continue;
+            }
+            if node == search.goal {
+                return Err(item.path);
+            }
+            let depth = item.path.len() as f64;
+            let frame = Frame::new(search, node.clone(), depth - 1., item.h, item.moves);
+            for (h, neighbor, moves) in frame.neighbors.into_iter().flatten() {
+                let score = search.mode.cost(depth, h);
+                if score > bound {
+                    *min = min.min(score);
+                } else if !greedy || !item.path.contains(&neighbor) {
+                    let mut path = item.path.clone();
+                    path.push(neighbor);
+                    next_items.push(WorkItem { path, h, moves });
+                }
+            }
+        }
+        items = next_items;
+    }
+    Ok(items)
+}
+
+// Each thread takes the next work item until all are done or the goal is found,
+// -- every solution under the bound is optimal since the bound is a lower bound of the cost
+fn parallel_iteration(
+    search: &Search,
+    summary: &mut Summary,
+    root: WorkItem,
+    bound: f64,
+    threads: usize,
+) -> (f64, Option<Vec<Board>>) {
+    let mut min = f64::INFINITY;
+    let items = match split(search, summary, root, bound, threads, &mut min) {
+        Ok(items) => items,
+        Err(path) => return (bound, Some(path)),
+    };
+    let next = AtomicUsize::new(0);
+    let min = Mutex::new(min);
+    let solution: Mutex<Option<Vec<Board>>> = Mutex::new(None);
+    let summaries: Vec<Summary> = thread::scope(|scope| {
+        let workers: Vec<_> = (0..threads)
+            .map(|_| {
+                scope.spawn(|| {
+                    let mut summary = Summary::default();
+                    while !search.stop.load(Ordering::Relaxed) {
+                        let index = next.fetch_add(1, Ordering::Relaxed);
+                        if index >= items.len() {
+                            break;
+                        }
+                        let item = &items[index];
+                        let mut item_summary = Summary::default();
+                        let (score, path) = iteration(search, &mut item_summary, item, bound);
+                        summary.add(&item_summary);
+                        summary.biggest_state = summary
+                            .biggest_state
+                            .max(item.path.len() - 1 + item_summary.biggest_state);
+                        if let Some(path) = path {
+                            solution.lock().unwrap().get_or_insert(path);
+                            search.stop.store(true, Ordering::Relaxed);
+                        } else {
+                            let mut min = min.lock().unwrap();
+                            *min = min.min(score);
+                        }
+                    }
+                    summary
+                })
+            })
+            .collect();
+        workers
+            .into_iter()
+            .map(|worker| worker.join().unwrap())
+            .collect()
+    });
+    for worker in summaries {
+        summary.add(&worker);
+        summary.biggest_state = summary.biggest_state.max(worker.biggest_state);
+    }
+    let min = *min.lock().unwrap();
+    (min, solution.into_inner().unwrap())
+}
+
+pub fn solve(puzzle: &Puzzle, config: &Config, heuristic: Heuristic) -> Result<Solution, String> {
     let now = Instant::now();
-    let mode = match mode {
-        "greedy" => Mode::Greedy,
-        "uniform" => Mode::Uniform,
-        _ => Mode::Normal,
+    let mode = config.mode();
+    let pruning = if config.move_pruning == "fsm" {
+        MovePruning::fsm()
+    } else {
+        MovePruning::inverse()
+    };
+    let map = Board::from(&puzzle.map[..]);
+    let goal = Board::from(&puzzle.goal[..]);
+    let stop = AtomicBool::new(false);
+    let table = config.tt_size.map(TranspositionTable::new);
+    let search = Search {
+        puzzle,
+        goal: &goal,
+        heuristic,
+        mode: &mode,
+        pruning: &pruning,
+        stop: &stop,
+        table: table.as_ref(),
     };
 
     // State
     let mut summary = Summary {
-        total_used_states: 0,
         biggest_state: 1, // 1 is the initial state
+        ..Summary::default()
     };
+    let h = heuristic.evaluate(puzzle.size, &map, &goal);
     let mut bound = match mode {
         Mode::Uniform => 1., // Ignore heuristic
-        _ => heuristic(puzzle.size, &puzzle.map, &puzzle.goal),
+        Mode::Weighted(weight) => weight * h,
+        _ => h,
     };
-    let mut path = vec![puzzle.map.clone()];
 
     loop {
-        let mut branch = Branch {
-            path: &mut path,
-            depth: 0.,
-            bound,
+        let root = WorkItem {
+            path: vec![map.clone()],
+            h,
+            moves: MovePruning::START,
         };
-        let result = evaluate_branch(puzzle, &mut summary, &mut branch, heuristic, &mode);
-        if result.result.is_some() {
+        let (score, path) = if config.threads > 1 {
+            parallel_iteration(&search, &mut summary, root, bound, config.threads)
+        } else {
+            iteration(&search, &mut summary, &root, bound)
+        };
+        if let Some(path) = path {
+            let steps: Vec<Vec<i32>> = path.iter().map(Board::to_vec).collect();
             return Ok(Solution {
                 biggest_state: summary.biggest_state,
                 total_used_states: summary.total_used_states,
-                steps: path,
+                moves: blank_moves(puzzle.size, &steps)?,
+                steps,
+                expanded_by_direction: None,
+                suboptimality_bound: mode.suboptimality_bound(&heuristic),
+                transposition_hits: table
+                    .as_ref()
+                    .map(|_| (summary.transposition_hits, summary.transposition_misses)),
+                layer_histogram: None,
             });
         }
-        if result.score == f64::INFINITY {
+        if score == f64::INFINITY {
             return Err(String::from("Failed to find a solution for this puzzle"));
         }
-        println!(
+        config.progress(&format!(
             "#> Explored {} states to bound {} in {:.2?}",
             summary.total_used_states,
             bound,
             now.elapsed()
-        );
-        bound = result.score;
+        ));
+        bound = score;
+        if let Some(table) = &table {
+            table.next_iteration();
+        }
+    }
+}
+
+#[test]
+fn weighted_is_bounded() {
+    let config = Config {
+        mode: "weighted".to_string(),
+        weight: Some(2.),
+        ..Config::default()
+    };
+    crate::puzzle::check_solutions(2, |puzzle| {
+        let solution = solve(puzzle, &config, crate::heuristic::MANHATTAN).unwrap();
+        assert_eq!(solution.suboptimality_bound, Some(2.));
+        solution
+    });
+}
+
+#[test]
+fn fsm_keeps_optimal() {
+    let config = Config {
+        move_pruning: "fsm".to_string(),
+        ..Config::default()
+    };
+    crate::puzzle::check_solutions(1, |puzzle| {
+        solve(puzzle, &config, crate::heuristic::MANHATTAN).unwrap()
+    });
+}
+
+#[test]
+fn greedy_large_puzzle() {
+    use rand::rngs::StdRng;
+    use rand::SeedableRng;
+    let config = Config {
+        mode: "greedy".to_string(),
+        ..Config::default()
+    };
+    let mut rng = StdRng::seed_from_u64(5);
+    let puzzle = Puzzle::generate(true, 5, 5, "snail", &mut rng).unwrap();
+    let solution = solve(&puzzle, &config, crate::heuristic::MANHATTAN).unwrap();
+    assert_eq!(*solution.steps.last().unwrap(), puzzle.goal);
+}
+
+#[test]
+fn parallel_is_optimal() {
+    let config = Config {
+        threads: 4,
+        ..Config::default()
+    };
+    crate::puzzle::check_solutions(1, |puzzle| {
+        solve(puzzle, &config, crate::heuristic::MANHATTAN).unwrap()
+    });
+}
+
+#[test]
+fn transposition_table_is_optimal() {
+    for move_pruning in ["inverse", "fsm"] {
+        let config = Config {
+            tt_size: Some(1 << 20),
+            move_pruning: move_pruning.to_string(),
+            ..Config::default()
+        };
+        crate::puzzle::check_solutions(1, |puzzle| {
+            let solution = solve(puzzle, &config, crate::heuristic::MANHATTAN).unwrap();
+            assert!(solution.transposition_hits.is_some());
+            solution
+        });
+    }
+}
+
+#[test]
+fn rectangular_puzzles() {
+    let linear_conflicts = Heuristic::incremental(
+        crate::heuristic::linear_conflicts,
+        crate::heuristic::linear_conflicts_update,
+    );
+    let walking_distance = Heuristic::new(crate::heuristic::walking_distance);
+    // Walking distance tables only exist up to 4 rows and columns
+    for (path, optimal, heuristic) in [
+        ("puzzles/solv_2x4_1.map", 22, linear_conflicts),
+        ("puzzles/solv_2x4_1.map", 22, walking_distance),
+        ("puzzles/solv_3x5_1.map", 30, linear_conflicts),
+    ] {
+        let puzzle = Puzzle::new(path, "snail").unwrap();
+        let solution = solve(&puzzle, &Config::default(), heuristic).unwrap();
+        assert_eq!(solution.steps.len() - 1, optimal, "{}", path);
+        assert_eq!(*solution.steps.last().unwrap(), puzzle.goal);
+    }
+}
+
+#[test]
+fn custom_goal() {
+    use rand::rngs::StdRng;
+    use rand::SeedableRng;
+    let (size, _, goal) = crate::goal::load("puzzles/goal_3_1.map").unwrap();
+    let table = crate::lookup_table::get(&Board::from(&goal[..]));
+    // Linear conflicts is the default heuristic
+    let linear_conflicts = Heuristic::incremental(
+        crate::heuristic::linear_conflicts,
+        crate::heuristic::linear_conflicts_update,
+    );
+    let mut rng = StdRng::seed_from_u64(20);
+    for _ in 0..10 {
+        let puzzle = Puzzle::generate_for_goal(true, size, goal.clone(), &mut rng);
+        assert!(puzzle.is_solvable());
+        let optimal = table.distance(&Board::from(&puzzle.map[..]));
+        for heuristic in [crate::heuristic::MANHATTAN, linear_conflicts] {
+            let solution = solve(&puzzle, &Config::default(), heuristic).unwrap();
+            assert_eq!(Some(solution.steps.len() as u8 - 1), optimal);
+            assert_eq!(*solution.steps.last().unwrap(), goal);
+        }
     }
 }
@@ -1,5 +1,14 @@
 use std::cmp::Ordering;
 
+mod board;
+mod moves;
+pub use board::Board;
+pub use moves::{
+    blank_moves, blank_to_tile_moves, format_blank_moves, format_tile_moves, parse_blank_moves,
+    parse_tile_moves, tile_to_blank_moves,
+};
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
 pub enum Direction {
     Left,
     Right,
@@ -8,7 +17,8 @@ pub enum Direction {
 }
 
 struct Border {
-    max: i32,
+    max_x: i32,
+    max_y: i32,
     left: i32,
     top: i32,
     right: i32,
@@ -31,9 +41,14 @@ pub struct SnailIterator {
 
 impl SnailIterator {
     pub fn new(size: i32) -> SnailIterator {
+        SnailIterator::rectangle(size, size)
+    }
+
+    // Snail of a board with width columns and height rows
+    pub fn rectangle(width: i32, height: i32) -> SnailIterator {
         SnailIterator {
-            size,
-            max: (size * size),
+            size: width,
+            max: (width * height),
             cursor: Cursor {
                 x: 0,
                 y: 0,
@@ -41,7 +56,8 @@ impl SnailIterator {
                 value: 1,
             },
             border: Border {
-                max: size - 1,
+                max_x: width - 1,
+                max_y: height - 1,
                 left: 0,
                 top: 0,
                 right: 0,
@@ -68,13 +84,13 @@ impl Iterator for SnailIterator {
             self.cursor.value += 1;
             match self.cursor.direction {
                 Direction::Right => {
-                    if self.cursor.x == self.border.max - self.border.right {
+                    if self.cursor.x == self.border.max_x - self.border.right {
                         self.cursor.direction = Direction::Down;
                         self.border.top += 1;
                     }
                 }
                 Direction::Down => {
-                    if self.cursor.y == self.border.max - self.border.bottom {
+                    if self.cursor.y == self.border.max_y - self.border.bottom {
                         self.cursor.direction = Direction::Left;
                         self.border.right += 1;
                     }
@@ -111,44 +127,32 @@ impl Iterator for SnailIterator {
     }
 }
 
-pub fn neighbors(size: i32, source: &[i32]) -> [Option<Vec<i32>>; 4] {
-    let index: i32 = source.iter().position(|&cell| cell == 0).unwrap() as i32;
+pub fn neighbors(size: i32, source: &Board) -> [Option<Board>; 4] {
+    let index = source.blank();
+    let size: usize = size.try_into().unwrap();
     let mut neighbors = [
         // Left
         {
-            if index == 0 || index % size == 0 {
+            if index.is_multiple_of(size) {
                 None
             } else {
-                let mut cpy = Vec::from(source);
-                let index: usize = index.try_into().unwrap();
-                cpy[index] = cpy[index - 1];
-                cpy[index - 1] = 0;
-                Some(cpy)
+                Some(source.slide(index - 1))
             }
         },
         // Right
         {
-            if (index + 1) % size == 0 {
+            if (index + 1).is_multiple_of(size) {
                 None
             } else {
-                let mut cpy = Vec::from(source);
-                let index: usize = index.try_into().unwrap();
-                cpy[index] = cpy[index + 1];
-                cpy[index + 1] = 0;
-                Some(cpy)
+                Some(source.slide(index + 1))
             }
         },
         // Down
         {
-            if index >= (size * (size - 1)) {
+            if index + size >= source.len() {
                 None
             } else {
-                let size: usize = size.try_into().unwrap();
-                let mut cpy = Vec::from(source);
-                let index: usize = index.try_into().unwrap();
-                cpy[index] = cpy[index + size];
-                cpy[index + size] = 0;
-                Some(cpy)
+                Some(source.slide(index + size))
             }
         },
         // Up
@@ -156,12 +160,7 @@ pub fn neighbors(size: i32, source: &[i32]) -> [Option<Vec<i32>>; 4] {
             if index < size {
                 None
             } else {
-                let size: usize = size.try_into().unwrap();
-                let mut cpy = Vec::from(source);
-                let index: usize = index.try_into().unwrap();
-                cpy[index] = cpy[index - size];
-                cpy[index - size] = 0;
-                Some(cpy)
+                Some(source.slide(index - size))
             }
         },
     ];
@@ -171,64 +170,146 @@ pub fn neighbors(size: i32, source: &[i32]) -> [Option<Vec<i32>>; 4] {
 
 #[test]
 fn first_cell() {
-    let source = vec![0, 1, 2, 3, 4, 5, 6, 7, 8];
+    let source = Board::from(vec![0, 1, 2, 3, 4, 5, 6, 7, 8]);
     let neighbors = neighbors(3, &source);
-    assert_eq!(neighbors[0], Some(vec![3, 1, 2, 0, 4, 5, 6, 7, 8]));
-    assert_eq!(neighbors[1], Some(vec![1, 0, 2, 3, 4, 5, 6, 7, 8]));
+    assert_eq!(
+        neighbors[0],
+        Some(Board::from(vec![3, 1, 2, 0, 4, 5, 6, 7, 8]))
+    );
+    assert_eq!(
+        neighbors[1],
+        Some(Board::from(vec![1, 0, 2, 3, 4, 5, 6, 7, 8]))
+    );
     assert_eq!(neighbors[2], None);
     assert_eq!(neighbors[3], None);
 }
 
 #[test]
 fn first_line_center() {
-    let source = vec![1, 0, 2, 3, 4, 5, 6, 7, 8];
+    let source = Board::from(vec![1, 0, 2, 3, 4, 5, 6, 7, 8]);
     let neighbors = neighbors(3, &source);
-    assert_eq!(neighbors[0], Some(vec![1, 4, 2, 3, 0, 5, 6, 7, 8]));
-    assert_eq!(neighbors[1], Some(vec![1, 2, 0, 3, 4, 5, 6, 7, 8]));
-    assert_eq!(neighbors[2], Some(vec![0, 1, 2, 3, 4, 5, 6, 7, 8]));
+    assert_eq!(
+        neighbors[0],
+        Some(Board::from(vec![1, 4, 2, 3, 0, 5, 6, 7, 8]))
+    );
+    assert_eq!(
+        neighbors[1],
+        Some(Board::from(vec![1, 2, 0, 3, 4, 5, 6, 7, 8]))
+    );
+    assert_eq!(
+        neighbors[2],
+        Some(Board::from(vec![0, 1, 2, 3, 4, 5, 6, 7, 8]))
+    );
     assert_eq!(neighbors[3], None);
 }
 
 #[test]
 fn middle_line_right() {
-    let source = vec![1, 4, 2, 3, 5, 0, 6, 7, 8];
+    let source = Board::from(vec![1, 4, 2, 3, 5, 0, 6, 7, 8]);
     let neighbors = neighbors(3, &source);
-    assert_eq!(neighbors[0], Some(vec![1, 4, 2, 3, 5, 8, 6, 7, 0]));
-    assert_eq!(neighbors[1], Some(vec![1, 4, 2, 3, 0, 5, 6, 7, 8]));
-    assert_eq!(neighbors[2], Some(vec![1, 4, 0, 3, 5, 2, 6, 7, 8]));
+    assert_eq!(
+        neighbors[0],
+        Some(Board::from(vec![1, 4, 2, 3, 5, 8, 6, 7, 0]))
+    );
+    assert_eq!(
+        neighbors[1],
+        Some(Board::from(vec![1, 4, 2, 3, 0, 5, 6, 7, 8]))
+    );
+    assert_eq!(
+        neighbors[2],
+        Some(Board::from(vec![1, 4, 0, 3, 5, 2, 6, 7, 8]))
+    );
     assert_eq!(neighbors[3], None);
 }
 
 #[test]
 fn last_line() {
-    let source = vec![1, 4, 2, 3, 5, 6, 0, 7, 8];
+    let source = Board::from(vec![1, 4, 2, 3, 5, 6, 0, 7, 8]);
     let neighbors = neighbors(3, &source);
-    assert_eq!(neighbors[0], Some(vec![1, 4, 2, 3, 5, 6, 7, 0, 8]));
-    assert_eq!(neighbors[1], Some(vec![1, 4, 2, 0, 5, 6, 3, 7, 8]));
+    assert_eq!(
+        neighbors[0],
+        Some(Board::from(vec![1, 4, 2, 3, 5, 6, 7, 0, 8]))
+    );
+    assert_eq!(
+        neighbors[1],
+        Some(Board::from(vec![1, 4, 2, 0, 5, 6, 3, 7, 8]))
+    );
     assert_eq!(neighbors[2], None);
     assert_eq!(neighbors[3], None);
 }
 
 #[test]
 fn last_line_first_column() {
-    let source = vec![6, 7, 5, 4, 1, 8, 0, 2, 3];
+    let source = Board::from(vec![6, 7, 5, 4, 1, 8, 0, 2, 3]);
     let neighbors = neighbors(3, &source);
-    assert_eq!(neighbors[0], Some(vec![6, 7, 5, 4, 1, 8, 2, 0, 3]));
-    assert_eq!(neighbors[1], Some(vec![6, 7, 5, 0, 1, 8, 4, 2, 3]));
+    assert_eq!(
+        neighbors[0],
+        Some(Board::from(vec![6, 7, 5, 4, 1, 8, 2, 0, 3]))
+    );
+    assert_eq!(
+        neighbors[1],
+        Some(Board::from(vec![6, 7, 5, 0, 1, 8, 4, 2, 3]))
+    );
     assert_eq!(neighbors[2], None);
     assert_eq!(neighbors[3], None);
 }
 
+#[test]
+fn rectangle_last_line() {
+    // 2 rows of 4 columns
+    let source = Board::from(vec![1, 2, 3, 4, 5, 0, 6, 7]);
+    let neighbors = neighbors(4, &source);
+    assert_eq!(
+        neighbors[0],
+        Some(Board::from(vec![1, 2, 3, 4, 5, 6, 0, 7]))
+    );
+    assert_eq!(
+        neighbors[1],
+        Some(Board::from(vec![1, 2, 3, 4, 0, 5, 6, 7]))
+    );
+    assert_eq!(
+        neighbors[2],
+        Some(Board::from(vec![1, 0, 3, 4, 5, 2, 6, 7]))
+    );
+    assert_eq!(neighbors[3], None);
+}
+
 pub enum Mode {
     Normal,
     Greedy,
     Uniform,
+    // f(n) = g(n) + w * h(n)
+    Weighted(f64),
+}
+
+impl Mode {
+    // f(n) of a node with the cost g(n) of its path and the estimate h(n) of the remaining cost
+    pub fn cost(&self, g: f64, h: f64) -> f64 {
+        match self {
+            Mode::Normal => g + h,
+            Mode::Greedy => h,  // Ignore depth
+            Mode::Uniform => g, // Ignore heuristic
+            Mode::Weighted(weight) => g + weight * h,
+        }
+    }
+
+    // How many times longer than the optimal solution a solution of an exact search can be,
+    // -- there is no bound in greedy mode or when the heuristic can overestimate
+    pub fn suboptimality_bound(&self, heuristic: &Heuristic) -> Option<f64> {
+        match self {
+            Mode::Uniform => Some(1.), // Ignore heuristic
+            _ if !heuristic.is_admissible() => None,
+            Mode::Normal => Some(1.),
+            Mode::Greedy => None,
+            Mode::Weighted(weight) => Some(*weight),
+        }
+    }
 }
 
 #[derive(Clone)]
 pub struct NodeWithCost {
     pub cost: f64,
-    pub node: Vec<i32>,
+    pub node: Board,
 }
 
 impl Eq for NodeWithCost {}
@@ -241,13 +322,14 @@ impl PartialEq for NodeWithCost {
 
 impl Ord for NodeWithCost {
     fn cmp(&self, other: &Self) -> Ordering {
-        other.cost.partial_cmp(&self.cost).unwrap().reverse()
+        // Reversed so that the BinaryHeap pops the lowest cost first
+        other.cost.partial_cmp(&self.cost).unwrap()
     }
 }
 
 impl PartialOrd for NodeWithCost {
     fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
-        Some(self.cmp(other).reverse())
+        Some(self.cmp(other))
     }
 }
 
@@ -255,9 +337,78 @@ pub struct Solution {
     pub total_used_states: usize,
     pub biggest_state: usize,
     pub steps: Vec<Vec<i32>>,
+    // Move of the empty cell between each step
+    pub moves: Vec<Direction>,
+    // Forward and backward expanded states, for bidirectional searches
+    pub expanded_by_direction: Option<(usize, usize)>,
+    pub suboptimality_bound: Option<f64>,
+    // Hits and misses of the transposition table, for searches using one
+    pub transposition_hits: Option<(usize, usize)>,
+    // Amount of states at each distance from the start, for breadth-first searches
+    pub layer_histogram: Option<Vec<usize>>,
 }
 
-pub type HeuristicFn = fn(i32, &[i32], &[i32]) -> f64;
+pub type HeuristicFn = fn(i32, &Board, &Board) -> f64;
+// Value of a child from the value of its parent,
+// -- only the tile moved to the empty cell of the parent changed between them
+pub type UpdateFn = fn(i32, f64, &Board, &Board, &Board) -> f64;
+
+#[derive(Clone, Copy)]
+pub struct Heuristic {
+    full: HeuristicFn,
+    incremental: Option<UpdateFn>,
+    // Never overestimates the distance to the goal, for any goal
+    admissible: bool,
+}
+
+impl Heuristic {
+    pub const fn new(full: HeuristicFn) -> Heuristic {
+        Heuristic {
+            full,
+            incremental: None,
+            admissible: true,
+        }
+    }
+
+    pub const fn incremental(full: HeuristicFn, update: UpdateFn) -> Heuristic {
+        Heuristic {
+            full,
+            incremental: Some(update),
+            admissible: true,
+        }
+    }
+
+    // Solutions found with a heuristic that can overestimate have no bound
+    pub const fn inadmissible(self) -> Heuristic {
+        Heuristic {
+            admissible: false,
+            ..self
+        }
+    }
+
+    pub fn is_admissible(&self) -> bool {
+        self.admissible
+    }
+
+    pub fn evaluate(&self, size: i32, node: &Board, goal: &Board) -> f64 {
+        (self.full)(size, node, goal)
+    }
+
+    // Value of a neighbor of parent, without a full evaluation when the heuristic allows it
+    pub fn update(
+        &self,
+        size: i32,
+        value: f64,
+        parent: &Board,
+        child: &Board,
+        goal: &Board,
+    ) -> f64 {
+        match self.incremental {
+            Some(update) => update(size, value, parent, child, goal),
+            None => (self.full)(size, child, goal),
+        }
+    }
+}
 
 pub fn print_map(size: i32, map: &[i32]) {
     let size: usize = size.try_into().unwrap();
@@ -0,0 +1,317 @@
+use crate::config::Config;
+use crate::pattern_database::{checksum, goal_checksum, Pattern, Reader, Source};
+use crate::puzzle::Puzzle;
+use npuzzle::{blank_moves, neighbors, Board, Heuristic, Solution};
+use std::collections::VecDeque;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex};
+
+// Only the 3x3 state space is small enough to be enumerated
+pub const SIZE: i32 = 3;
+pub const CELLS: usize = (SIZE * SIZE) as usize;
+const UNREACHABLE: u8 = u8::MAX;
+const FILE_MAGIC: &[u8; 4] = b"NLUT";
+const FILE_VERSION: u8 = 1;
+
+// Every table built or loaded during this run
+static TABLES: Mutex<Vec<Arc<LookupTable>>> = Mutex::new(Vec::new());
+
+// Number of permutations of the cells, each one is ranked like a pattern of every tile
+fn states() -> usize {
+    (1..=CELLS).product()
+}
+
+fn rank(board: &Board) -> usize {
+    let mut positions = [0; CELLS];
+    for (index, cell) in board.iter().enumerate() {
+        positions[cell as usize] = index;
+    }
+    Pattern::rank(CELLS, &positions)
+}
+
+// Board from the position of each tile
+fn unrank(positions: &[usize; CELLS]) -> Board {
+    let mut cells = [0; CELLS];
+    for (tile, &position) in positions.iter().enumerate() {
+        cells[position] = tile as i32;
+    }
+    Board::from(&cells[..])
+}
+
+// Exact distance to the goal of every state, unsolvable states are unreachable
+pub struct LookupTable {
+    pub goal: Board,
+    pub distances: Vec<u8>,
+}
+
+impl LookupTable {
+    // Breadth-first search from the goal over every permutation
+    pub fn build(goal: &Board) -> LookupTable {
+        let mut distances = vec![UNREACHABLE; states()];
+        let mut queue = VecDeque::new();
+        let start = rank(goal);
+        distances[start] = 0;
+        queue.push_back(start as u32);
+        let mut positions = [0; CELLS];
+        while let Some(current) = queue.pop_front() {
+            let distance = distances[current as usize];
+            Pattern::unrank(CELLS, current as usize, &mut positions);
+            let board = unrank(&positions);
+            for neighbor in neighbors(SIZE, &board).into_iter().flatten() {
+                let next = rank(&neighbor);
+                if distances[next] == UNREACHABLE {
+                    distances[next] = distance + 1;
+                    queue.push_back(next as u32);
+                }
+            }
+        }
+        LookupTable {
+            goal: goal.clone(),
+            distances,
+        }
+    }
+
+    pub fn distance(&self, board: &Board) -> Option<u8> {
+        match self.distances[rank(board)] {
+            UNREACHABLE => None,
+            distance => Some(distance),
+        }
+    }
+
+    // Every solvable state with its distance
+    pub fn states(&self) -> impl Iterator<Item = (Board, u8)> + '_ {
+        self.distances
+            .iter()
+            .enumerate()
+            .filter(|(_, &distance)| distance != UNREACHABLE)
+            .map(|(rank, &distance)| {
+                let mut positions = [0; CELLS];
+                Pattern::unrank(CELLS, rank, &mut positions);
+                (unrank(&positions), distance)
+            })
+    }
+
+    // Amount of states at each distance from the goal
+    pub fn histogram(&self) -> Vec<usize> {
+        let mut histogram = vec![];
+        for &distance in self.distances.iter().filter(|&&d| d != UNREACHABLE) {
+            let distance = distance as usize;
+            if histogram.len() <= distance {
+                histogram.resize(distance + 1, 0);
+            }
+            histogram[distance] += 1;
+        }
+        histogram
+    }
+
+    pub fn file_name(goal: &Board) -> String {
+        format!("lookup-{}-{:016x}.bin", SIZE, goal_checksum(goal))
+    }
+
+    // Little endian binary format:
+    // magic, version, goal checksum, goal cells, the distance of each rank,
+    // -- and the checksum of everything before it
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let mut bytes = Vec::from(&FILE_MAGIC[..]);
+        bytes.push(FILE_VERSION);
+        bytes.extend(goal_checksum(&self.goal).to_le_bytes());
+        for cell in self.goal.iter() {
+            bytes.extend(cell.to_le_bytes());
+        }
+        bytes.extend(&self.distances);
+        bytes.extend(checksum(&bytes).to_le_bytes());
+        bytes
+    }
+
+    // Parse a table and check that it was built for the given goal
+    pub fn from_bytes(bytes: &[u8], goal: &Board) -> Result<LookupTable, String> {
+        if bytes.len() < 8 {
+            return Err("File is too small".to_string());
+        }
+        let (content, expected) = bytes.split_at(bytes.len() - 8);
+        if checksum(content) != u64::from_le_bytes(expected.try_into().unwrap()) {
+            return Err("Invalid checksum, the file is corrupted".to_string());
+        }
+
+        let mut reader = Reader {
+            bytes: content,
+            offset: 0,
+        };
+        if reader.take(4)? != FILE_MAGIC {
+            return Err("Not a lookup table file".to_string());
+        }
+        let version = reader.take(1)?[0];
+        if version != FILE_VERSION {
+            return Err(format!("Unsupported file version {}", version));
+        }
+        if reader.u64()? != goal_checksum(goal) {
+            return Err("Table built for another goal".to_string());
+        }
+        let mut file_goal = Vec::with_capacity(CELLS);
+        for _ in 0..CELLS {
+            file_goal.push(reader.i32()?);
+        }
+        let file_goal = Board::from(file_goal);
+        if file_goal != *goal {
+            return Err("Table built for another goal".to_string());
+        }
+        let distances = Vec::from(reader.take(states())?);
+        if reader.offset != content.len() {
+            return Err("Invalid table length".to_string());
+        }
+
+        Ok(LookupTable {
+            goal: file_goal,
+            distances,
+        })
+    }
+
+    pub fn save(&self, path: &Path) -> Result<(), String> {
+        fs::write(path, self.to_bytes())
+            .map_err(|err| format!("Failed to write {}: {}", path.display(), err))
+    }
+
+    pub fn load(path: &Path, goal: &Board) -> Result<LookupTable, String> {
+        let bytes =
+            fs::read(path).map_err(|err| format!("Failed to read {}: {}", path.display(), err))?;
+        LookupTable::from_bytes(&bytes, goal)
+            .map_err(|err| format!("Invalid lookup table {}: {}", path.display(), err))
+    }
+}
+
+fn find(goal: &Board) -> Option<Arc<LookupTable>> {
+    TABLES
+        .lock()
+        .unwrap()
+        .iter()
+        .find(|table| table.goal == *goal)
+        .cloned()
+}
+
+fn register(table: LookupTable) -> Arc<LookupTable> {
+    let table = Arc::new(table);
+    TABLES.lock().unwrap().push(table.clone());
+    table
+}
+
+// Find or build the table for the given goal
+pub fn get(goal: &Board) -> Arc<LookupTable> {
+    find(goal).unwrap_or_else(|| register(LookupTable::build(goal)))
+}
+
+// Use the table saved in the directory if there is one,
+// -- or build it and save it for the next runs
+pub fn load_or_build(directory: &Path, goal: &Board) -> Result<Source, String> {
+    if find(goal).is_some() {
+        return Ok(Source::Memory);
+    }
+    let path = directory.join(LookupTable::file_name(goal));
+    let mut error = None;
+    if path.exists() {
+        match LookupTable::load(&path, goal) {
+            Ok(table) => {
+                register(table);
+                return Ok(Source::Disk(path));
+            }
+            Err(err) => error = Some(err),
+        }
+    }
+
+    let table = LookupTable::build(goal);
+    fs::create_dir_all(directory)
+        .map_err(|err| format!("Failed to create {}: {}", directory.display(), err))?;
+    table.save(&path)?;
+    register(table);
+    match error {
+        Some(err) => Ok(Source::Rebuilt(path, err)),
+        None => Ok(Source::Built(path)),
+    }
+}
+
+// Build the table of a goal and always save it, even if one already exists
+pub fn enumerate(directory: &Path, goal: &Board) -> Result<(Arc<LookupTable>, PathBuf), String> {
+    let table = LookupTable::build(goal);
+    let path = directory.join(LookupTable::file_name(goal));
+    fs::create_dir_all(directory)
+        .map_err(|err| format!("Failed to create {}: {}", directory.display(), err))?;
+    table.save(&path)?;
+    Ok((register(table), path))
+}
+
+// Follow the states whose distance decreases by one until the goal, ignoring the heuristic
+pub fn solve(puzzle: &Puzzle, _: &Config, _: Heuristic) -> Result<Solution, String> {
+    if puzzle.size != SIZE || puzzle.height != SIZE {
+        return Err(format!(
+            "Lookup tables only exist for {}x{} puzzles",
+            SIZE, SIZE
+        ));
+    }
+    let goal = Board::from(&puzzle.goal[..]);
+    let table = get(&goal);
+    let mut current = Board::from(&puzzle.map[..]);
+    let mut distance = table
+        .distance(&current)
+        .ok_or_else(|| String::from("Failed to find a solution for this puzzle"))?;
+    let mut steps = vec![current.to_vec()];
+    while distance > 0 {
+        current = neighbors(SIZE, &current)
+            .into_iter()
+            .flatten()
+            .find(|neighbor| table.distance(neighbor) == Some(distance - 1))
+            .unwrap();
+        distance -= 1;
+        steps.push(current.to_vec());
+    }
+
+    Ok(Solution {
+        total_used_states: steps.len(),
+        biggest_state: 1,
+        moves: blank_moves(SIZE, &steps)?,
+        steps,
+        expanded_by_direction: None,
+        suboptimality_bound: Some(1.),
+        transposition_hits: None,
+        layer_histogram: None,
+    })
+}
+
+#[test]
+fn every_solvable_state() {
+    let goal = Board::from(vec![1, 2, 3, 8, 0, 4, 7, 6, 5]);
+    let table = LookupTable::build(&goal);
+    let histogram = table.histogram();
+    assert_eq!(histogram.iter().sum::<usize>(), 181_440);
+    assert_eq!(histogram[0], 1);
+    assert_eq!(histogram.len() - 1, 30);
+    for (path, optimal) in crate::puzzle::OPTIMAL_LENGTHS {
+        let puzzle = Puzzle::new(path, "snail").unwrap();
+        assert_eq!(
+            table.distance(&Board::from(&puzzle.map[..])),
+            Some(optimal as u8),
+            "{}",
+            path
+        );
+    }
+}
+
+#[test]
+fn finds_optimal_length() {
+    crate::puzzle::check_solutions(1, |puzzle| {
+        solve(puzzle, &Config::default(), crate::heuristic::MANHATTAN).unwrap()
+    });
+}
+
+#[test]
+fn save_and_load() {
+    let goal = Board::from(vec![1, 2, 3, 4, 5, 6, 7, 8, 0]);
+    let table = LookupTable::build(&goal);
+    let mut bytes = table.to_bytes();
+    let loaded = LookupTable::from_bytes(&bytes, &goal).unwrap();
+    assert_eq!(loaded.distances, table.distances);
+    let other = Board::from(vec![0, 1, 2, 3, 4, 5, 6, 7, 8]);
+    assert!(LookupTable::from_bytes(&bytes, &other).is_err());
+    let middle = bytes.len() / 2;
+    bytes[middle] ^= 1;
+    assert!(LookupTable::from_bytes(&bytes, &goal).is_err());
+}
@@ -1,59 +1,232 @@
-use npuzzle::{print_map, HeuristicFn, Solution};
+use json::Json;
+use npuzzle::{
+    blank_to_tile_moves, format_blank_moves, format_tile_moves, print_map, Board, Heuristic,
+    Solution,
+};
+use pattern_database::Source;
 use puzzle::Puzzle;
+use rand::rngs::StdRng;
+use rand::SeedableRng;
+use std::path::Path;
 use std::process;
-use std::time::Instant;
+use std::time::{Duration, Instant};
 
 mod a_star;
+mod ara_star;
+mod bench;
+mod bidir_a_star;
 mod config;
+mod disk_bfs;
 mod goal;
+mod hda_star;
 mod heuristic;
+mod heuristic_check;
 mod ida_star;
+mod json;
+mod lookup_table;
+mod move_pruning;
+mod pattern_database;
 mod puzzle;
+mod reduction;
+mod sma_star;
+mod transposition_table;
+mod verify;
+mod walking_distance;
 
-type SolveFn = fn(&Puzzle, &str, fn(i32, &[i32], &[i32]) -> f64) -> Result<Solution, String>;
+type SolveFn = fn(&Puzzle, &config::Config, Heuristic) -> Result<Solution, String>;
 
 fn solve_by_name(name: &str) -> Option<SolveFn> {
     if name == "ida*" {
         return Some(ida_star::solve);
     } else if name == "a*" {
         return Some(a_star::solve);
+    } else if name == "bidir-a*" {
+        return Some(bidir_a_star::solve);
+    } else if name == "ara*" {
+        return Some(ara_star::solve);
+    } else if name == "hda*" {
+        return Some(hda_star::solve);
+    } else if name == "sma*" {
+        return Some(sma_star::solve);
+    } else if name == "disk-bfs" {
+        return Some(disk_bfs::solve);
+    } else if name == "lookup" {
+        return Some(lookup_table::solve);
+    } else if name == "reduction" {
+        return Some(reduction::solve);
     }
     None
 }
 
-fn heuristic_by_name(name: &str) -> Option<HeuristicFn> {
+// Every heuristic that can be given to --heuristic
+const HEURISTICS: [&str; 6] = [
+    "manhattan",
+    "euclidean",
+    "hamming",
+    "linear-conflicts",
+    "pdb",
+    "walking-distance",
+];
+
+fn heuristic_by_name(name: &str) -> Option<Heuristic> {
     if name == "manhattan" {
-        return Some(heuristic::manhattan);
+        return Some(heuristic::MANHATTAN);
     } else if name == "euclidean" {
-        return Some(heuristic::euclidean_distance);
+        return Some(Heuristic::incremental(
+            heuristic::euclidean_distance,
+            heuristic::euclidean_distance_update,
+        ));
     } else if name == "hamming" {
-        return Some(heuristic::hamming);
+        return Some(Heuristic::incremental(
+            heuristic::hamming,
+            heuristic::hamming_update,
+        ));
     } else if name == "linear-conflicts" {
-        return Some(heuristic::linear_conflicts);
+        return Some(Heuristic::incremental(
+            heuristic::linear_conflicts,
+            heuristic::linear_conflicts_update,
+        ));
+    } else if name == "pdb" {
+        return Some(Heuristic::new(heuristic::pattern_database));
+    } else if name == "walking-distance" {
+        return Some(Heuristic::new(heuristic::walking_distance));
     }
     None
 }
 
+// What happened to a puzzle, printed for humans or as JSON
+enum Outcome {
+    Unsolvable,
+    // The configuration can't be used for this puzzle
+    Unsupported(String),
+    Error(String),
+    Solved(Solution, Duration),
+    Failed(Duration),
+}
+
 fn solve_puzzle(
     config: &config::Config,
     puzzle: &Puzzle,
     solve_fn: SolveFn,
-    heuristic_fn: HeuristicFn,
-) {
-    println!("{}", puzzle);
-    print_map(puzzle.size, &puzzle.goal);
-
+    heuristic_fn: Heuristic,
+) -> Outcome {
     if !Puzzle::is_solvable(puzzle) {
-        println!("#> Puzzle is unsolvable for this solution");
-        return;
+        return Outcome::Unsolvable;
+    }
+
+    // The reduction variant only uses the heuristic on the region left once the lines are placed,
+    // -- and the variants that compute or read the exact distances don't use it
+    let (heuristic_size, heuristic_goal) = if config.variant == "reduction" {
+        reduction::remainder_goal(puzzle)
+    } else {
+        (puzzle.size, puzzle.goal.clone())
+    };
+    let heuristic_height = heuristic_goal.len() as i32 / heuristic_size;
+    let uses_heuristic = config.variant != "disk-bfs" && config.variant != "lookup";
+
+    if uses_heuristic
+        && config.heuristic_name == "walking-distance"
+        && heuristic_size.max(heuristic_height) > walking_distance::MAX_SIZE
+    {
+        return Outcome::Unsupported(format!(
+            "Walking distance can't be used for puzzle with more than {} rows or columns",
+            walking_distance::MAX_SIZE
+        ));
+    }
+
+    if config.variant == "lookup"
+        && (puzzle.size != lookup_table::SIZE || puzzle.height != lookup_table::SIZE)
+    {
+        return Outcome::Unsupported(format!(
+            "Lookup tables only exist for {}x{} puzzles",
+            lookup_table::SIZE,
+            lookup_table::SIZE
+        ));
+    }
+
+    if uses_heuristic
+        && config.heuristic_name == "pdb"
+        && heuristic_goal.len() > pattern_database::MAX_CELLS
+    {
+        return Outcome::Unsupported(format!(
+            "Pattern databases can't be used for puzzles with more than {} cells",
+            pattern_database::MAX_CELLS
+        ));
+    }
+
+    // Build the pattern database before solving to not include it in the solve time
+    if uses_heuristic && config.heuristic_name == "pdb" {
+        let now = Instant::now();
+        let goal = Board::from(&heuristic_goal[..]);
+        if let Some(pdb_dir) = &config.pdb_dir {
+            match pattern_database::load_or_build(Path::new(pdb_dir), heuristic_size, &goal) {
+                Ok(Source::Memory) => {}
+                Ok(Source::Disk(path)) => config.progress(&format!(
+                    "#> Pattern database loaded from {}",
+                    path.display()
+                )),
+                Ok(Source::Built(path)) => {
+                    config.progress(&format!("#> Pattern database saved to {}", path.display()))
+                }
+                Ok(Source::Rebuilt(path, err)) => {
+                    eprintln!("#> {}", err);
+                    config.progress(&format!(
+                        "#> Pattern database replaced in {}",
+                        path.display()
+                    ));
+                }
+                Err(err) => return Outcome::Error(err),
+            }
+        } else {
+            pattern_database::get(heuristic_size, &goal);
+        }
+        config.progress(&format!(
+            "#> Pattern database ready in {:.2?}",
+            now.elapsed()
+        ));
+    }
+
+    // Same for the lookup table
+    if config.variant == "lookup" {
+        let now = Instant::now();
+        let goal = Board::from(&puzzle.goal[..]);
+        if let Some(lookup_dir) = &config.lookup_dir {
+            match lookup_table::load_or_build(Path::new(lookup_dir), &goal) {
+                Ok(Source::Memory) => {}
+                Ok(Source::Disk(path)) => {
+                    config.progress(&format!("#> Lookup table loaded from {}", path.display()))
+                }
+                Ok(Source::Built(path)) => {
+                    config.progress(&format!("#> Lookup table saved to {}", path.display()))
+                }
+                Ok(Source::Rebuilt(path, err)) => {
+                    eprintln!("#> {}", err);
+                    config.progress(&format!("#> Lookup table replaced in {}", path.display()));
+                }
+                Err(err) => return Outcome::Error(err),
+            }
+        } else {
+            lookup_table::get(&goal);
+        }
+        config.progress(&format!("#> Lookup table ready in {:.2?}", now.elapsed()));
     }
 
     let now = Instant::now();
-    let res = solve_fn(puzzle, &config.mode, heuristic_fn);
+    let res = solve_fn(puzzle, config, heuristic_fn);
     let elapsed = now.elapsed();
     match res {
-        // Ok(solution) => println!("#> Solution {:#?}", solution),
-        Ok(solution) => {
+        Ok(solution) => Outcome::Solved(solution, elapsed),
+        Err(_) => Outcome::Failed(elapsed),
+    }
+}
+
+fn print_outcome(config: &config::Config, puzzle: &Puzzle, outcome: &Outcome) {
+    match outcome {
+        Outcome::Unsolvable => println!("#> Puzzle is unsolvable for this solution"),
+        Outcome::Unsupported(message) => println!("#> {}", message),
+        Outcome::Error(err) => eprintln!("#> {}", err),
+        Outcome::Failed(elapsed) => eprintln!("#> No solution found in {:.2?}", elapsed),
+        Outcome::Solved(solution, elapsed) => {
             println!(
                 "#> Solution found ({:#?} {}) in {:.2?}",
                 solution.steps.len() - 1,
@@ -72,13 +245,439 @@ fn solve_puzzle(
                 "#> Maximum number of states ever represented in memory: {}",
                 solution.biggest_state
             );
-            let size: usize = puzzle.size.try_into().unwrap();
-            for (index, step) in solution.steps.iter().enumerate() {
-                println!("{:<3} {}", index, "#".repeat((index % size) + 1));
-                print_map(puzzle.size, step);
+            // Only exact searches with an admissible heuristic can tell
+            match solution.suboptimality_bound {
+                Some(bound) if bound > 1. => println!(
+                    "#> Solution is at most {:.3} times longer than the optimal solution",
+                    bound
+                ),
+                Some(_) => println!("#> Solution is optimal"),
+                None => {}
+            }
+            if let Some((forward, backward)) = solution.expanded_by_direction {
+                println!(
+                    "#> States expanded forward: {}, backward: {}",
+                    forward, backward
+                );
+            }
+            if let Some((hits, misses)) = solution.transposition_hits {
+                println!("#> Transposition table hits: {}, misses: {}", hits, misses);
+            }
+            if let Some(histogram) = &solution.layer_histogram {
+                for (distance, states) in histogram.iter().enumerate() {
+                    println!("#> Distance {:>3}: {} states", distance, states);
+                }
+            }
+            if config.output == "moves" {
+                let tiles = blank_to_tile_moves(puzzle.size, &puzzle.map, &solution.moves).unwrap();
+                println!("#> Blank moves: {}", format_blank_moves(&solution.moves));
+                println!("#> Tile moves: {}", format_tile_moves(&tiles));
+            } else {
+                let size: usize = puzzle.size.try_into().unwrap();
+                for (index, step) in solution.steps.iter().enumerate() {
+                    println!("{:<3} {}", index, "#".repeat((index % size) + 1));
+                    print_map(puzzle.size, step);
+                }
+            }
+        }
+    }
+}
+
+// The puzzle, the configuration and the solution as a single JSON object
+fn json_outcome(config: &config::Config, name: &str, puzzle: &Puzzle, outcome: &Outcome) -> Json {
+    let mut entries = vec![
+        ("puzzle", name.into()),
+        ("size", puzzle.size.into()),
+        ("height", puzzle.height.into()),
+        ("board", Json::from(&puzzle.map[..])),
+        ("goal", Json::from(&puzzle.goal[..])),
+        ("solvable", (!matches!(outcome, Outcome::Unsolvable)).into()),
+        ("variant", config.variant.as_str().into()),
+        ("heuristic", config.heuristic_name.as_str().into()),
+        ("mode", config.mode.as_str().into()),
+    ];
+    match outcome {
+        Outcome::Unsolvable => {}
+        Outcome::Unsupported(message) | Outcome::Error(message) => {
+            entries.push(("error", message.as_str().into()))
+        }
+        Outcome::Failed(elapsed) => {
+            entries.push(("elapsed", elapsed.as_secs_f64().into()));
+            entries.push(("error", "No solution found".into()));
+        }
+        Outcome::Solved(solution, elapsed) => {
+            let moves: Vec<Json> = solution
+                .moves
+                .iter()
+                .map(|direction| direction.letter().to_string().into())
+                .collect();
+            let tiles = blank_to_tile_moves(puzzle.size, &puzzle.map, &solution.moves).unwrap();
+            entries.extend([
+                ("elapsed", elapsed.as_secs_f64().into()),
+                ("total_used_states", solution.total_used_states.into()),
+                ("biggest_state", solution.biggest_state.into()),
+                ("suboptimality_bound", solution.suboptimality_bound.into()),
+                ("length", solution.moves.len().into()),
+                ("moves", Json::Array(moves)),
+                ("tiles", Json::from(&tiles[..])),
+            ]);
+            if let Some((forward, backward)) = solution.expanded_by_direction {
+                entries.push((
+                    "expanded_by_direction",
+                    Json::Object(vec![
+                        ("forward", forward.into()),
+                        ("backward", backward.into()),
+                    ]),
+                ));
+            }
+            if let Some((hits, misses)) = solution.transposition_hits {
+                entries.push((
+                    "transposition_hits",
+                    Json::Object(vec![("hits", hits.into()), ("misses", misses.into())]),
+                ));
+            }
+            if let Some(histogram) = &solution.layer_histogram {
+                entries.push(("layer_histogram", Json::from(&histogram[..])));
+            }
+            if config.output == "boards" {
+                let steps = solution
+                    .steps
+                    .iter()
+                    .map(|step| Json::from(&step[..]))
+                    .collect();
+                entries.push(("steps", Json::Array(steps)));
+            }
+        }
+    }
+    Json::Object(entries)
+}
+
+// Solve a puzzle and print its outcome, returns whether it was solved
+fn run_puzzle(
+    config: &config::Config,
+    name: &str,
+    puzzle: Result<Puzzle, String>,
+    solve_fn: SolveFn,
+    heuristic_fn: Heuristic,
+) -> bool {
+    let json = config.format == "json";
+    if !json {
+        println!("# {}", name);
+    }
+    let puzzle = match puzzle {
+        Ok(puzzle) => puzzle,
+        Err(err) => {
+            if json {
+                let entries = vec![("puzzle", name.into()), ("error", err.into())];
+                println!("{}", Json::Object(entries));
+            } else {
+                eprintln!("#> {}", err);
+            }
+            return false;
+        }
+    };
+    if !json {
+        println!("{}", puzzle);
+        print_map(puzzle.size, &puzzle.goal);
+    }
+    let outcome = solve_puzzle(config, &puzzle, solve_fn, heuristic_fn);
+    if json {
+        println!("{}", json_outcome(config, name, &puzzle, &outcome));
+    } else {
+        print_outcome(config, &puzzle, &outcome);
+    }
+    matches!(outcome, Outcome::Solved(..))
+}
+
+// Enumerate every 3x3 state and save the exact distance of each to the goal
+fn enumerate(config: &config::Config) {
+    let goal = match &config.goal {
+        Some(goal) if goal.len() == lookup_table::CELLS && config.size == lookup_table::SIZE => {
+            Ok(goal.clone())
+        }
+        Some(_) => Err(format!(
+            "Lookup tables only exist for {}x{} puzzles",
+            lookup_table::SIZE,
+            lookup_table::SIZE
+        )),
+        None => goal::generate(lookup_table::SIZE, &config.solution_type),
+    }
+    .unwrap_or_else(|err| {
+        eprintln!("{}", err);
+        process::exit(1);
+    });
+    println!("# Goal ({})", config.goal_name());
+    print_map(lookup_table::SIZE, &goal);
+
+    let now = Instant::now();
+    let directory = config
+        .lookup_dir
+        .as_deref()
+        .unwrap_or(config::DEFAULT_LOOKUP_DIR);
+    match lookup_table::enumerate(Path::new(directory), &Board::from(goal)) {
+        Ok((table, path)) => {
+            let histogram = table.histogram();
+            println!(
+                "#> {} states enumerated in {:.2?}",
+                histogram.iter().sum::<usize>(),
+                now.elapsed()
+            );
+            for (distance, states) in histogram.iter().enumerate() {
+                println!("#> Distance {:>3}: {} states", distance, states);
+            }
+            println!("#> Lookup table saved to {}", path.display());
+        }
+        Err(err) => {
+            eprintln!("#> {}", err);
+            process::exit(1);
+        }
+    }
+}
+
+// Compare every heuristic with the exact distance of the boards around the goal
+fn check_heuristics(config: &config::Config) {
+    let goal = match &config.goal {
+        Some(goal) => Ok(goal.clone()),
+        None => goal::generate_rectangle(config.size, config.height, &config.solution_type),
+    }
+    .unwrap_or_else(|err| {
+        eprintln!("{}", err);
+        process::exit(1);
+    });
+    let goal = Board::from(goal);
+    println!("# Goal ({})", config.goal_name());
+    print_map(config.size, &goal.to_vec());
+
+    let now = Instant::now();
+    let max_distance = config
+        .max_distance
+        .unwrap_or(heuristic_check::DEFAULT_MAX_DISTANCE);
+    let boards = heuristic_check::exact_distances(config.size, &goal, max_distance);
+    if goal.len() == lookup_table::CELLS {
+        println!(
+            "#> {} boards enumerated in {:.2?}",
+            boards.len(),
+            now.elapsed()
+        );
+    } else {
+        println!(
+            "#> {} boards up to {} moves from the goal enumerated in {:.2?}",
+            boards.len(),
+            max_distance,
+            now.elapsed()
+        );
+    }
+
+    for name in HEURISTICS {
+        if name == "walking-distance" && config.size.max(config.height) > walking_distance::MAX_SIZE
+        {
+            continue;
+        }
+        if name == "pdb" && goal.len() > pattern_database::MAX_CELLS {
+            continue;
+        }
+        println!("# {}", name);
+        let now = Instant::now();
+        let heuristic = heuristic_by_name(name).unwrap();
+        let report = heuristic_check::check(config.size, &goal, heuristic, &boards);
+        println!(
+            "#> Admissibility violations: {} of {} boards",
+            report.admissibility_violations, report.boards
+        );
+        if let Some((overestimate, board)) = &report.worst_overestimate {
+            println!("#> Worst overestimate: {:.3} moves on", overestimate);
+            print_map(config.size, &board.to_vec());
+        }
+        println!(
+            "#> Consistency violations: {} of {} moves",
+            report.consistency_violations, report.moves
+        );
+        println!(
+            "#> Average accuracy: {:.1}% of the exact distance",
+            report.accuracy() * 100.
+        );
+        println!("#> Checked in {:.2?}", now.elapsed());
+    }
+}
+
+// Apply a move sequence to a puzzle and check that it reaches the goal
+fn verify(config: &config::Config) {
+    if config.files.len() < 2 {
+        eprintln!("Expected a puzzle path followed by the moves to verify");
+        process::exit(1);
+    }
+    let puzzle_path = &config.files[0];
+    let puzzle = match &config.goal {
+        Some(goal) => Puzzle::with_goal(puzzle_path, goal, config.size),
+        None => Puzzle::new(puzzle_path, &config.solution_type),
+    }
+    .unwrap_or_else(|err| {
+        eprintln!("#> {}", err);
+        process::exit(1);
+    });
+    println!("# {}", puzzle_path);
+    println!("{}", puzzle);
+    print_map(puzzle.size, &puzzle.goal);
+
+    let moves = verify::parse_moves(&config.files[1..].join(" ")).unwrap_or_else(|err| {
+        eprintln!("#> Malformed moves: {}", err);
+        process::exit(1);
+    });
+    let replay = verify::replay(&puzzle, &moves);
+    println!("#> {} of {} moves applied", replay.applied, moves.len());
+    if let Some((_, err)) = &replay.illegal {
+        eprintln!("#> Illegal move: {}", err);
+    }
+    if replay.reached_goal {
+        println!("#> Goal reached in {} moves", replay.applied);
+    } else {
+        println!("#> Goal not reached, the last board is");
+        print_map(puzzle.size, &replay.board);
+        process::exit(1);
+    }
+}
+
+// Next random puzzle for the goal of --solution-type or --goal-file
+fn generate_puzzle(config: &config::Config, rng: &mut StdRng) -> Result<Puzzle, String> {
+    match &config.goal {
+        Some(goal) => Ok(Puzzle::generate_for_goal(
+            config.solvable,
+            config.size,
+            goal.clone(),
+            rng,
+        )),
+        None => Puzzle::generate(
+            config.solvable,
+            config.size,
+            config.height,
+            &config.solution_type,
+            rng,
+        ),
+    }
+}
+
+// Solve the same puzzles with every combination of the listed variants, heuristics and modes
+fn bench(config: &config::Config) {
+    let mut rng = StdRng::seed_from_u64(config.seed);
+    let puzzles: Result<Vec<(String, Puzzle)>, String> = if config.files.is_empty() {
+        (1..=config.amount)
+            .map(|i| {
+                let puzzle = generate_puzzle(config, &mut rng)?;
+                Ok((format!("Random Puzzle [{}]", i), puzzle))
+            })
+            .collect()
+    } else {
+        config
+            .files
+            .iter()
+            .map(|puzzle_path| {
+                let puzzle = match &config.goal {
+                    Some(goal) => Puzzle::with_goal(puzzle_path, goal, config.size),
+                    None => Puzzle::new(puzzle_path, &config.solution_type),
+                };
+                Ok((puzzle_path.clone(), puzzle?))
+            })
+            .collect()
+    };
+    let puzzles = puzzles.unwrap_or_else(|err| {
+        eprintln!("#> {}", err);
+        process::exit(1);
+    });
+
+    let variants: Vec<&str> = config.variant.split(',').collect();
+    let heuristics: Vec<&str> = config.heuristic_name.split(',').collect();
+    let modes: Vec<&str> = config.mode.split(',').collect();
+    for variant in &variants {
+        if solve_by_name(variant).is_none() {
+            eprintln!("Unknown variant: {}", variant);
+            process::exit(1);
+        }
+    }
+    for heuristic in &heuristics {
+        if heuristic_by_name(heuristic).is_none() {
+            eprintln!("Unknown heuristic: {}", heuristic);
+            process::exit(1);
+        }
+    }
+    for mode in &modes {
+        if !config::MODES.contains(mode) {
+            eprintln!("Unknown mode: {}", mode);
+            process::exit(1);
+        }
+    }
+    println!(
+        "# Bench of {} puzzles, {} runs each",
+        puzzles.len(),
+        config.runs
+    );
+    if config.files.is_empty() {
+        println!("# Seed {}", config.seed);
+    }
+    for (name, puzzle) in &puzzles {
+        println!("# {}", name);
+        println!("{}", puzzle);
+    }
+
+    let mut rows = Vec::new();
+    for variant in &variants {
+        for heuristic in &heuristics {
+            for mode in &modes {
+                let mut run_config = config.clone();
+                run_config.variant = variant.to_string();
+                run_config.heuristic_name = heuristic.to_string();
+                run_config.mode = mode.to_string();
+                run_config.quiet = true;
+                let solve_fn = solve_by_name(variant).unwrap();
+                let heuristic_fn = heuristic_by_name(heuristic).unwrap();
+
+                let now = Instant::now();
+                let mut row = bench::Row::new(variant, heuristic, mode);
+                'puzzles: for (_, puzzle) in &puzzles {
+                    for _ in 0..config.runs {
+                        row.runs += 1;
+                        let start_memory = bench::start_peak_memory();
+                        match solve_puzzle(&run_config, puzzle, solve_fn, heuristic_fn) {
+                            Outcome::Solved(solution, elapsed) => {
+                                let peak_memory = bench::peak_memory(start_memory);
+                                row.samples.push(bench::Sample::new(
+                                    &solution,
+                                    elapsed,
+                                    peak_memory,
+                                ))
+                            }
+                            Outcome::Unsolvable => {
+                                row.error = Some("Puzzle is unsolvable".to_string())
+                            }
+                            Outcome::Failed(_) => row.error = Some("No solution found".to_string()),
+                            // The other runs of this puzzle would fail the same way
+                            Outcome::Unsupported(err) | Outcome::Error(err) => {
+                                row.error = Some(err);
+                                continue 'puzzles;
+                            }
+                        }
+                    }
+                }
+                println!(
+                    "#> {} {} {}: {} of {} runs solved in {:.2?}",
+                    variant,
+                    heuristic,
+                    mode,
+                    row.samples.len(),
+                    row.runs,
+                    now.elapsed()
+                );
+                rows.push(row);
             }
         }
-        Err(_) => eprintln!("#> No solution found in {:.2?}", elapsed),
+    }
+
+    bench::print_table(&rows);
+    if let Some(csv) = &config.csv {
+        if let Err(err) = bench::write_csv(Path::new(csv), &rows) {
+            eprintln!("#> {}", err);
+            process::exit(1);
+        }
+        println!("#> Results saved to {}", csv);
     }
 }
 
@@ -87,6 +686,13 @@ fn main() {
         eprintln!("Failed to generate config: {}", err);
         process::exit(1);
     });
+    match config.command.as_deref() {
+        Some("enumerate") => return enumerate(&config),
+        Some("check-heuristics") => return check_heuristics(&config),
+        Some("verify") => return verify(&config),
+        Some("bench") => return bench(&config),
+        _ => {}
+    }
 
     // Check config values
     let heuristic_fn = heuristic_by_name(&config.heuristic_name).unwrap_or_else(|| {
@@ -103,49 +709,47 @@ fn main() {
 
     //  Solve each puzzles
     let now = Instant::now();
+    let mut solved = 0;
+    let mut rng = StdRng::seed_from_u64(config.seed);
     if config.files.is_empty() {
-        if config.variant == "ida*" && config.mode == "greedy" && config.size > 4 {
-            println!(
-                "> Greedy mode can't be used with IDA* for puzzle larger than 4 (Stack Overflow)"
-            );
-            return;
-        }
         for i in 1..=config.amount {
-            println!("# Random Puzzle [{}]", i);
-            let puzzle = Puzzle::generate(config.solvable, config.size, &config.solution_type);
-            if let Err(err) = puzzle {
-                eprintln!("#> {}", err);
-            } else {
-                solve_puzzle(&config, &puzzle.unwrap(), solve_fn, heuristic_fn);
+            let puzzle = generate_puzzle(&config, &mut rng);
+            let name = format!("Random Puzzle [{}]", i);
+            if run_puzzle(&config, &name, puzzle, solve_fn, heuristic_fn) {
+                solved += 1;
             }
         }
     } else {
         for puzzle_path in &config.files {
-            println!("# {}", puzzle_path);
-            let puzzle = Puzzle::new(puzzle_path, &config.solution_type);
-            if let Err(err) = puzzle {
-                eprintln!("#> {}", err);
-            } else {
-                let puzzle = puzzle.unwrap();
-                if config.variant == "ida*" && config.mode == "greedy" && puzzle.size > 4 {
-                    println!("#> Greedy mode can't be used with IDA* for puzzle larger than 4 (Stack Overflow)");
-                    return;
-                }
-                solve_puzzle(&config, &puzzle, solve_fn, heuristic_fn);
+            let puzzle = match &config.goal {
+                Some(goal) => Puzzle::with_goal(puzzle_path, goal, config.size),
+                None => Puzzle::new(puzzle_path, &config.solution_type),
+            };
+            if run_puzzle(&config, puzzle_path, puzzle, solve_fn, heuristic_fn) {
+                solved += 1;
             }
         }
     }
 
-    // Total duration if there was multiple puzzles
-    if (config.files.is_empty() && config.amount > 1) || config.files.len() > 1 {
-        println!(
-            "> Solved {} puzzles in {:.2?}",
-            if config.files.is_empty() {
-                config.amount
-            } else {
-                config.files.len() as u32
-            },
-            now.elapsed()
-        );
+    let puzzles = if config.files.is_empty() {
+        config.amount as usize
+    } else {
+        config.files.len()
+    };
+    if config.format == "json" {
+        let mut summary = vec![
+            ("puzzles", puzzles.into()),
+            ("solved", solved.into()),
+            ("elapsed", now.elapsed().as_secs_f64().into()),
+        ];
+        if config.files.is_empty() {
+            // JSON numbers can't hold every 64 bits seed
+            summary.push(("seed", config.seed.to_string().into()));
+        }
+        let summary = Json::Object(summary);
+        println!("{}", Json::Object(vec![("summary", summary)]));
+    } else if puzzles > 1 {
+        // Total duration if there was multiple puzzles
+        println!("> Solved {} puzzles in {:.2?}", puzzles, now.elapsed());
     }
 }
@@ -0,0 +1,232 @@
+use npuzzle::Board;
+use std::collections::{HashMap, HashSet, VecDeque};
+
+// Length of the move sequences compared to find the redundant ones
+const FSM_DEPTH: usize = 8;
+
+// Displacement of the empty cell for each direction: up, down, left, right
+const MOVES: [(i32, i32); 4] = [(0, -1), (0, 1), (-1, 0), (1, 0)];
+
+// Direction in which the empty cell moved between a parent and its child
+pub fn direction(size: i32, parent: &Board, child: &Board) -> usize {
+    let offset = child.blank() as i32 - parent.blank() as i32;
+    if offset == -size {
+        0
+    } else if offset == size {
+        1
+    } else if offset == -1 {
+        2
+    } else {
+        3
+    }
+}
+
+// Sequence of moves of the empty cell on an infinite board, starting from (0, 0)
+struct Sequence {
+    moves: Vec<u8>,
+    blank: (i32, i32),
+    // Tiles that are not on their starting cell, each tile is named by its starting cell
+    moved: Vec<((i32, i32), (i32, i32))>,
+    // Smallest and biggest x and y visited by the empty cell
+    bounds: [i32; 4],
+}
+
+impl Sequence {
+    fn tile_at(&self, cell: (i32, i32)) -> (i32, i32) {
+        self.moved
+            .iter()
+            .find(|(position, _)| *position == cell)
+            .map(|(_, tile)| *tile)
+            .unwrap_or(cell)
+    }
+
+    fn push(&self, direction: usize) -> Sequence {
+        let (x, y) = MOVES[direction];
+        let blank = (self.blank.0 + x, self.blank.1 + y);
+        let tile = self.tile_at(blank);
+        let mut moved: Vec<((i32, i32), (i32, i32))> = self
+            .moved
+            .iter()
+            .filter(|(position, _)| *position != self.blank && *position != blank)
+            .copied()
+            .collect();
+        moved.push((self.blank, tile));
+        moved.push((blank, (0, 0)));
+        moved.retain(|(position, tile)| position != tile);
+        moved.sort();
+        let mut moves = self.moves.clone();
+        moves.push(direction as u8);
+        Sequence {
+            moves,
+            blank,
+            moved,
+            bounds: [
+                self.bounds[0].min(blank.0),
+                self.bounds[1].max(blank.0),
+                self.bounds[2].min(blank.1),
+                self.bounds[3].max(blank.1),
+            ],
+        }
+    }
+
+    // Every move of a sequence within bounds can be used when the moves of this one can be used
+    fn contains(&self, bounds: &[i32; 4]) -> bool {
+        bounds[0] >= self.bounds[0]
+            && bounds[1] <= self.bounds[1]
+            && bounds[2] >= self.bounds[2]
+            && bounds[3] <= self.bounds[3]
+    }
+}
+
+// Sequences of moves up to depth that lead to the same tiles as a shorter or earlier sequence,
+// -- the other sequence must be usable from every cell where the redundant one is,
+// -- and sequences containing a redundant sequence are not checked since they are never used
+fn redundant_sequences(depth: usize) -> Vec<Vec<u8>> {
+    let start = Sequence {
+        moves: vec![],
+        blank: (0, 0),
+        moved: vec![],
+        bounds: [0; 4],
+    };
+    // Bounds of the first sequence leading to each tiles
+    let mut seen = HashMap::new();
+    seen.insert((start.blank, start.moved.clone()), start.bounds);
+    let mut redundant: HashSet<Vec<u8>> = HashSet::new();
+    let mut level = vec![start];
+    for _ in 0..depth {
+        let mut next_level = vec![];
+        for sequence in level.iter() {
+            for direction in 0..MOVES.len() {
+                let next = sequence.push(direction);
+                if (1..next.moves.len()).any(|start| redundant.contains(&next.moves[start..])) {
+                    continue;
+                }
+                let key = (next.blank, next.moved.clone());
+                match seen.get(&key) {
+                    Some(first) if next.contains(first) => {
+                        redundant.insert(next.moves);
+                    }
+                    Some(_) => next_level.push(next),
+                    None => {
+                        seen.insert(key, next.bounds);
+                        next_level.push(next);
+                    }
+                }
+            }
+        }
+        level = next_level;
+    }
+    let mut redundant: Vec<Vec<u8>> = redundant.into_iter().collect();
+    redundant.sort();
+    redundant
+}
+
+// Automaton reading the moves of a path, that reaches a forbidden state
+// -- when the last moves are a redundant sequence
+pub struct MovePruning {
+    transitions: Vec<[usize; 4]>,
+    forbidden: Vec<bool>,
+    // Length of the longest redundant sequence that was checked
+    depth: usize,
+}
+
+impl MovePruning {
+    pub const START: usize = 0;
+
+    // Only forbid moving back to the previous node
+    pub fn inverse() -> MovePruning {
+        MovePruning::new(&redundant_sequences(2), 2)
+    }
+
+    // Forbid every redundant sequence found with a finite state machine up to FSM_DEPTH moves
+    pub fn fsm() -> MovePruning {
+        MovePruning::new(&redundant_sequences(FSM_DEPTH), FSM_DEPTH)
+    }
+
+    // Aho-Corasick automaton of the sequences
+    fn new(sequences: &[Vec<u8>], depth: usize) -> MovePruning {
+        let mut transitions: Vec<[Option<usize>; 4]> = vec![[None; 4]];
+        let mut forbidden = vec![false];
+        for sequence in sequences {
+            let mut state = MovePruning::START;
+            for &direction in sequence {
+                state = match transitions[state][direction as usize] {
+                    Some(next) => next,
+                    None => {
+                        transitions.push([None; 4]);
+                        forbidden.push(false);
+                        transitions[state][direction as usize] = Some(transitions.len() - 1);
+                        transitions.len() - 1
+                    }
+                };
+            }
+            forbidden[state] = true;
+        }
+
+        // Missing transitions follow the longest suffix that is also a prefix of a sequence
+        let mut complete = vec![[MovePruning::START; 4]; transitions.len()];
+        let mut fallback = vec![MovePruning::START; transitions.len()];
+        let mut queue = VecDeque::from([MovePruning::START]);
+        while let Some(state) = queue.pop_front() {
+            for direction in 0..MOVES.len() {
+                match transitions[state][direction] {
+                    Some(next) => {
+                        if state != MovePruning::START {
+                            fallback[next] = complete[fallback[state]][direction];
+                        }
+                        forbidden[next] = forbidden[next] || forbidden[fallback[next]];
+                        complete[state][direction] = next;
+                        queue.push_back(next);
+                    }
+                    None => complete[state][direction] = complete[fallback[state]][direction],
+                }
+            }
+        }
+
+        MovePruning {
+            transitions: complete,
+            forbidden,
+            depth,
+        }
+    }
+
+    // Only the previous move is used to prune the next one
+    pub fn is_inverse(&self) -> bool {
+        self.depth <= 2
+    }
+
+    // State after the move, or None if the move should not be used
+    pub fn next(&self, state: usize, direction: usize) -> Option<usize> {
+        let next = self.transitions[state][direction];
+        if self.forbidden[next] {
+            None
+        } else {
+            Some(next)
+        }
+    }
+}
+
+#[test]
+fn inverse_moves() {
+    assert_eq!(
+        redundant_sequences(2),
+        vec![vec![0, 1], vec![1, 0], vec![2, 3], vec![3, 2]]
+    );
+    let pruning = MovePruning::inverse();
+    let state = pruning.next(MovePruning::START, 0).unwrap();
+    assert_eq!(pruning.next(state, 1), None);
+    assert!(pruning.next(state, 0).is_some());
+}
+
+#[test]
+fn fsm_forbids_loops() {
+    let redundant = redundant_sequences(FSM_DEPTH);
+    assert!(redundant.len() > 4);
+    // Going three times around a square of 4 cells gives back the same tiles
+    let pruning = MovePruning::fsm();
+    let mut state = Some(MovePruning::START);
+    for direction in [3, 1, 2, 0].repeat(3) {
+        state = state.and_then(|state| pruning.next(state, direction));
+    }
+    assert_eq!(state, None);
+}
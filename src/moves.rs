@@ -0,0 +1,210 @@
+use crate::Direction;
+
+const DIRECTIONS: [Direction; 4] = [
+    Direction::Up,
+    Direction::Down,
+    Direction::Left,
+    Direction::Right,
+];
+
+impl Direction {
+    // Standard notation of a move of the empty cell
+    pub fn letter(&self) -> char {
+        match self {
+            Direction::Up => 'U',
+            Direction::Down => 'D',
+            Direction::Left => 'L',
+            Direction::Right => 'R',
+        }
+    }
+
+    pub fn from_letter(letter: char) -> Option<Direction> {
+        match letter.to_ascii_uppercase() {
+            'U' => Some(Direction::Up),
+            'D' => Some(Direction::Down),
+            'L' => Some(Direction::Left),
+            'R' => Some(Direction::Right),
+            _ => None,
+        }
+    }
+
+    // Cell reached by the empty cell from blank, if it stays in a board with size columns
+    fn target(&self, size: usize, cells: usize, blank: usize) -> Option<usize> {
+        match self {
+            Direction::Up => blank.checked_sub(size),
+            Direction::Down => (blank + size < cells).then(|| blank + size),
+            Direction::Left => (!blank.is_multiple_of(size)).then(|| blank - 1),
+            Direction::Right => (!(blank + 1).is_multiple_of(size)).then(|| blank + 1),
+        }
+    }
+}
+
+fn blank(board: &[i32]) -> usize {
+    board.iter().position(|&cell| cell == 0).unwrap()
+}
+
+// Moves of the empty cell between each step of a solution,
+// -- each step must only swap the empty cell with one of its neighbors
+pub fn blank_moves(size: i32, steps: &[Vec<i32>]) -> Result<Vec<Direction>, String> {
+    steps
+        .windows(2)
+        .enumerate()
+        .map(|(index, pair)| {
+            let blank = blank(&pair[0]);
+            DIRECTIONS
+                .into_iter()
+                .find(|direction| {
+                    direction
+                        .target(size as usize, pair[0].len(), blank)
+                        .is_some_and(|cell| {
+                            let mut next = pair[0].clone();
+                            next.swap(blank, cell);
+                            next == pair[1]
+                        })
+                })
+                .ok_or_else(|| {
+                    format!(
+                        "Step {} doesn't slide a single tile into the empty cell",
+                        index + 1
+                    )
+                })
+        })
+        .collect()
+}
+
+// Moves of the empty cell written as their letters, like `RDLU`
+pub fn format_blank_moves(moves: &[Direction]) -> String {
+    moves.iter().map(Direction::letter).collect()
+}
+
+pub fn parse_blank_moves(moves: &str) -> Result<Vec<Direction>, String> {
+    moves
+        .chars()
+        .filter(|letter| !letter.is_whitespace())
+        .map(|letter| {
+            Direction::from_letter(letter).ok_or_else(|| format!("Invalid move `{}`", letter))
+        })
+        .collect()
+}
+
+// Moves of the tiles written as the number of each moved tile, like `5 8 2`
+pub fn format_tile_moves(tiles: &[i32]) -> String {
+    tiles
+        .iter()
+        .map(|tile| tile.to_string())
+        .collect::<Vec<String>>()
+        .join(" ")
+}
+
+pub fn parse_tile_moves(tiles: &str) -> Result<Vec<i32>, String> {
+    tiles
+        .split(|c: char| c.is_whitespace() || c == ',')
+        .filter(|tile| !tile.is_empty())
+        .map(|tile| {
+            tile.parse()
+                .map_err(|err| format!("Invalid tile `{}`: {}", tile, err))
+        })
+        .collect()
+}
+
+// Number of the tile sliding into the empty cell for each move of the empty cell from the start
+pub fn blank_to_tile_moves(
+    size: i32,
+    start: &[i32],
+    moves: &[Direction],
+) -> Result<Vec<i32>, String> {
+    let mut board = start.to_vec();
+    let mut tiles = Vec::with_capacity(moves.len());
+    for (index, direction) in moves.iter().enumerate() {
+        let blank = blank(&board);
+        let cell = direction
+            .target(size as usize, board.len(), blank)
+            .ok_or_else(|| {
+                format!(
+                    "Move {} `{}` leaves the board",
+                    index + 1,
+                    direction.letter()
+                )
+            })?;
+        tiles.push(board[cell]);
+        board.swap(blank, cell);
+    }
+    Ok(tiles)
+}
+
+// Move of the empty cell for each tile sliding into it from the start
+pub fn tile_to_blank_moves(
+    size: i32,
+    start: &[i32],
+    tiles: &[i32],
+) -> Result<Vec<Direction>, String> {
+    let mut board = start.to_vec();
+    let mut moves = Vec::with_capacity(tiles.len());
+    for (index, &tile) in tiles.iter().enumerate() {
+        let blank = blank(&board);
+        let direction = DIRECTIONS
+            .into_iter()
+            .find(|direction| {
+                direction
+                    .target(size as usize, board.len(), blank)
+                    .is_some_and(|cell| board[cell] == tile && tile != 0)
+            })
+            .ok_or_else(|| {
+                format!(
+                    "Move {}: tile {} is not next to the empty cell",
+                    index + 1,
+                    tile
+                )
+            })?;
+        let cell = direction.target(size as usize, board.len(), blank).unwrap();
+        board.swap(blank, cell);
+        moves.push(direction);
+    }
+    Ok(moves)
+}
+
+#[test]
+fn both_conventions() {
+    // 2 rows of 3 columns
+    let start = vec![1, 2, 3, 4, 5, 0];
+    let steps = vec![
+        vec![1, 2, 3, 4, 5, 0],
+        vec![1, 2, 0, 4, 5, 3],
+        vec![1, 0, 2, 4, 5, 3],
+        vec![1, 5, 2, 4, 0, 3],
+        vec![1, 5, 2, 4, 3, 0],
+    ];
+    let moves = blank_moves(3, &steps).unwrap();
+    assert_eq!(format_blank_moves(&moves), "ULDR");
+    let tiles = blank_to_tile_moves(3, &start, &moves).unwrap();
+    assert_eq!(tiles, vec![3, 2, 5, 3]);
+    assert_eq!(format_tile_moves(&tiles), "3 2 5 3");
+    assert_eq!(tile_to_blank_moves(3, &start, &tiles).unwrap(), moves);
+    assert_eq!(parse_blank_moves("u l d r").unwrap(), moves);
+    assert_eq!(parse_tile_moves("3, 2, 5, 3").unwrap(), tiles);
+}
+
+#[test]
+fn invalid_moves() {
+    let start = vec![1, 2, 3, 4, 5, 6, 7, 8, 0];
+    assert!(blank_to_tile_moves(3, &start, &[Direction::Right]).is_err());
+    assert!(blank_to_tile_moves(3, &start, &[Direction::Down]).is_err());
+    assert!(tile_to_blank_moves(3, &start, &[5]).is_err());
+    assert!(tile_to_blank_moves(3, &start, &[0]).is_err());
+    assert!(parse_blank_moves("UX").is_err());
+    let step = |board: &[i32]| blank_moves(3, &[start.clone(), board.to_vec()]);
+    assert_eq!(step(&[1, 2, 3, 4, 5, 0, 7, 8, 6]), Ok(vec![Direction::Up]));
+    assert_eq!(
+        step(&[1, 2, 3, 4, 5, 6, 7, 0, 8]),
+        Ok(vec![Direction::Left])
+    );
+    // The empty cell jumps two cells, or moves up while other tiles change
+    assert!(step(&[1, 2, 3, 4, 5, 6, 0, 8, 7]).is_err());
+    assert!(step(&[1, 2, 3, 4, 5, 0, 7, 6, 8]).is_err());
+    // The empty cell wraps from the start of a line to the end of the previous one
+    let wrapped = vec![
+        vec![1, 2, 3, 0, 4, 5, 6, 7, 8],
+        vec![1, 2, 0, 3, 4, 5, 6, 7, 8],
+    ];
+    assert!(blank_moves(3, &wrapped).is_err());
+}
@@ -0,0 +1,522 @@
+use npuzzle::Board;
+use std::cell::RefCell;
+use std::collections::VecDeque;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex};
+
+// Biggest table allowed for a single pattern, in entries (1 byte each)
+const MAX_PATTERN_ENTRIES: u64 = 150_000_000;
+const MAX_PATTERN_TILES: usize = 6;
+// The cells used by the tiles of a pattern are kept in the bits of a u64
+pub const MAX_CELLS: usize = 64;
+const UNVISITED: u8 = u8::MAX;
+const FILE_MAGIC: &[u8; 4] = b"NPDB";
+const FILE_VERSION: u8 = 1;
+
+// Every database built during this run, shared between threads
+static DATABASES: Mutex<Vec<Arc<PatternDatabase>>> = Mutex::new(Vec::new());
+
+thread_local! {
+    // Last database used by the thread, to avoid locking on each evaluation
+    static CURRENT: RefCell<Option<Arc<PatternDatabase>>> = const { RefCell::new(None) };
+}
+
+// FNV-1a, used to identify goals and to detect corrupted files
+pub fn checksum(bytes: &[u8]) -> u64 {
+    bytes.iter().fold(0xcbf29ce484222325, |hash, &byte| {
+        (hash ^ byte as u64).wrapping_mul(0x100000001b3)
+    })
+}
+
+pub fn goal_checksum(goal: &Board) -> u64 {
+    let bytes: Vec<u8> = goal.iter().flat_map(|cell| cell.to_le_bytes()).collect();
+    checksum(&bytes)
+}
+
+// Number of k-permutations of n elements: n! / (n - k)!
+fn permutations(n: usize, k: usize) -> u64 {
+    ((n - k + 1)..=n).map(|value| value as u64).product()
+}
+
+pub struct Pattern {
+    pub tiles: Vec<i32>,
+    pub table: Vec<u8>,
+}
+
+impl Pattern {
+    // Perfect hash of the positions of each tiles of the pattern
+    // -- each position is ranked among the cells not already used by the previous tiles
+    pub fn rank(cells: usize, positions: &[usize]) -> usize {
+        let mut rank = 0;
+        let mut used: u64 = 0;
+        for (index, &position) in positions.iter().enumerate() {
+            let before = (used & ((1 << position) - 1)).count_ones() as usize;
+            rank = rank * (cells - index) + (position - before);
+            used |= 1 << position;
+        }
+        rank
+    }
+
+    pub fn unrank(cells: usize, mut rank: usize, positions: &mut [usize]) {
+        let tiles = positions.len();
+        for index in (0..tiles).rev() {
+            let radix = cells - index;
+            positions[index] = rank % radix;
+            rank /= radix;
+        }
+        // Convert each digit back to the n-th free cell
+        let mut used: u64 = 0;
+        for position in positions.iter_mut() {
+            let mut remaining = *position;
+            let mut cell = 0;
+            loop {
+                if used & (1 << cell) == 0 {
+                    if remaining == 0 {
+                        break;
+                    }
+                    remaining -= 1;
+                }
+                cell += 1;
+            }
+            used |= 1 << cell;
+            *position = cell;
+        }
+    }
+
+    // Breadth-first search from the goal positions of the pattern tiles
+    // -- a tile can move to any adjacent cell not used by another tile of the pattern,
+    // -- the other tiles are ignored which makes the cost of each pattern additive
+    fn build(size: i32, goal: &Board, tiles: Vec<i32>) -> Pattern {
+        let size = size as usize;
+        let cells = goal.len();
+        let height = cells / size;
+        let mut table = vec![UNVISITED; permutations(cells, tiles.len()) as usize];
+        let start: Vec<usize> = tiles
+            .iter()
+            .map(|&tile| goal.position(tile).unwrap())
+            .collect();
+
+        let mut queue = VecDeque::new();
+        let start_rank = Pattern::rank(cells, &start);
+        table[start_rank] = 0;
+        queue.push_back(start_rank as u32);
+        let mut positions = vec![0; tiles.len()];
+        let mut occupied = vec![false; cells];
+        while let Some(rank) = queue.pop_front() {
+            let cost = table[rank as usize];
+            Pattern::unrank(cells, rank as usize, &mut positions);
+            for &position in positions.iter() {
+                occupied[position] = true;
+            }
+            for index in 0..positions.len() {
+                let position = positions[index];
+                let (x, y) = (position % size, position / size);
+                let moves = [
+                    (x > 0, position.wrapping_sub(1)),
+                    (x + 1 < size, position + 1),
+                    (y + 1 < height, position + size),
+                    (y > 0, position.wrapping_sub(size)),
+                ];
+                for (valid, next) in moves {
+                    if !valid || occupied[next] {
+                        continue;
+                    }
+                    positions[index] = next;
+                    let next_rank = Pattern::rank(cells, &positions);
+                    if table[next_rank] == UNVISITED {
+                        table[next_rank] = cost + 1;
+                        queue.push_back(next_rank as u32);
+                    }
+                }
+                positions[index] = position;
+            }
+            for &position in positions.iter() {
+                occupied[position] = false;
+            }
+        }
+
+        Pattern { tiles, table }
+    }
+}
+
+pub struct PatternDatabase {
+    pub size: i32,
+    pub goal: Board,
+    pub patterns: Vec<Pattern>,
+}
+
+impl PatternDatabase {
+    // Amount of tiles in each pattern, as big as possible while keeping tables in memory
+    // -- 8 tiles gives 6-2 for 3x3, 15 gives 6-6-3 for 4x4 and 24 gives 6-6-6-6 for 5x5
+    pub fn layout(cells: usize) -> Vec<usize> {
+        let mut tiles_per_pattern = MAX_PATTERN_TILES;
+        while tiles_per_pattern > 1 && permutations(cells, tiles_per_pattern) > MAX_PATTERN_ENTRIES
+        {
+            tiles_per_pattern -= 1;
+        }
+        let remaining = (cells - 1) % tiles_per_pattern;
+        let mut layout = vec![tiles_per_pattern; (cells - 1) / tiles_per_pattern];
+        if remaining > 0 {
+            layout.push(remaining);
+        }
+        layout
+    }
+
+    // Group the tiles by their position in the goal, from top left to bottom right,
+    // -- which keeps the tiles of a pattern close to each others for any goal
+    pub fn partition(goal: &Board) -> Vec<Vec<i32>> {
+        let tiles: Vec<i32> = goal.iter().filter(|&cell| cell != 0).collect();
+        let mut partition = Vec::new();
+        let mut offset = 0;
+        for amount in PatternDatabase::layout(goal.len()) {
+            partition.push(tiles[offset..offset + amount].to_vec());
+            offset += amount;
+        }
+        partition
+    }
+
+    pub fn build(size: i32, goal: &Board) -> PatternDatabase {
+        let patterns = PatternDatabase::partition(goal)
+            .into_iter()
+            .map(|tiles| Pattern::build(size, goal, tiles))
+            .collect();
+        PatternDatabase {
+            size,
+            goal: goal.clone(),
+            patterns,
+        }
+    }
+
+    pub fn matches(&self, size: i32, goal: &Board) -> bool {
+        self.size == size && self.goal == *goal
+    }
+
+    // Sum of the cost of each pattern
+    pub fn evaluate(&self, node: &Board) -> u32 {
+        let cells = node.len();
+        let mut tile_positions = vec![0; cells];
+        for (index, cell) in node.iter().enumerate() {
+            tile_positions[cell as usize] = index;
+        }
+        let mut positions = [0; MAX_PATTERN_TILES];
+        self.patterns
+            .iter()
+            .map(|pattern| {
+                let positions = &mut positions[..pattern.tiles.len()];
+                for (position, &tile) in positions.iter_mut().zip(pattern.tiles.iter()) {
+                    *position = tile_positions[tile as usize];
+                }
+                pattern.table[Pattern::rank(cells, positions)] as u32
+            })
+            .sum()
+    }
+}
+
+// Sequential reader over the content of a database or lookup table file
+pub struct Reader<'a> {
+    pub bytes: &'a [u8],
+    pub offset: usize,
+}
+
+impl<'a> Reader<'a> {
+    pub fn take(&mut self, amount: usize) -> Result<&'a [u8], String> {
+        if self.offset + amount > self.bytes.len() {
+            return Err("Unexpected end of file".to_string());
+        }
+        let slice = &self.bytes[self.offset..self.offset + amount];
+        self.offset += amount;
+        Ok(slice)
+    }
+
+    pub fn u32(&mut self) -> Result<u32, String> {
+        Ok(u32::from_le_bytes(self.take(4)?.try_into().unwrap()))
+    }
+
+    pub fn i32(&mut self) -> Result<i32, String> {
+        Ok(i32::from_le_bytes(self.take(4)?.try_into().unwrap()))
+    }
+
+    pub fn u64(&mut self) -> Result<u64, String> {
+        Ok(u64::from_le_bytes(self.take(8)?.try_into().unwrap()))
+    }
+}
+
+impl PatternDatabase {
+    // Name of the file of the database, unique for each size, layout and goal
+    pub fn file_name(size: i32, goal: &Board) -> String {
+        let layout: Vec<String> = PatternDatabase::layout(goal.len())
+            .iter()
+            .map(|amount| amount.to_string())
+            .collect();
+        // Rectangular puzzles are named by their rows and columns
+        let height = goal.len() as i32 / size;
+        let dimensions = if height == size {
+            size.to_string()
+        } else {
+            format!("{}x{}", height, size)
+        };
+        format!(
+            "pdb-{}-{}-{:016x}.bin",
+            dimensions,
+            layout.join("-"),
+            goal_checksum(goal)
+        )
+    }
+
+    // Little endian binary format:
+    // magic, version, size, goal checksum, goal cells,
+    // -- amount of patterns, then for each pattern its tiles and its table,
+    // -- and the checksum of everything before it
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let mut bytes = Vec::from(&FILE_MAGIC[..]);
+        bytes.push(FILE_VERSION);
+        bytes.extend((self.size as u32).to_le_bytes());
+        bytes.extend(goal_checksum(&self.goal).to_le_bytes());
+        for cell in self.goal.iter() {
+            bytes.extend(cell.to_le_bytes());
+        }
+        bytes.extend((self.patterns.len() as u32).to_le_bytes());
+        for pattern in self.patterns.iter() {
+            bytes.extend((pattern.tiles.len() as u32).to_le_bytes());
+            for tile in pattern.tiles.iter() {
+                bytes.extend(tile.to_le_bytes());
+            }
+            bytes.extend((pattern.table.len() as u64).to_le_bytes());
+            bytes.extend(&pattern.table);
+        }
+        bytes.extend(checksum(&bytes).to_le_bytes());
+        bytes
+    }
+
+    // Parse a database and check that it was built for the given size and goal
+    pub fn from_bytes(bytes: &[u8], size: i32, goal: &Board) -> Result<PatternDatabase, String> {
+        if bytes.len() < 8 {
+            return Err("File is too small".to_string());
+        }
+        let (content, expected) = bytes.split_at(bytes.len() - 8);
+        if checksum(content) != u64::from_le_bytes(expected.try_into().unwrap()) {
+            return Err("Invalid checksum, the file is corrupted".to_string());
+        }
+
+        let mut reader = Reader {
+            bytes: content,
+            offset: 0,
+        };
+        if reader.take(4)? != FILE_MAGIC {
+            return Err("Not a pattern database file".to_string());
+        }
+        let version = reader.take(1)?[0];
+        if version != FILE_VERSION {
+            return Err(format!("Unsupported file version {}", version));
+        }
+        let file_size = reader.u32()? as i32;
+        if file_size != size {
+            return Err(format!(
+                "Database built for size {}, expected {}",
+                file_size, size
+            ));
+        }
+        if reader.u64()? != goal_checksum(goal) {
+            return Err("Database built for another goal".to_string());
+        }
+        let mut file_goal = Vec::with_capacity(goal.len());
+        for _ in 0..goal.len() {
+            file_goal.push(reader.i32()?);
+        }
+        let file_goal = Board::from(file_goal);
+        if file_goal != *goal {
+            return Err("Database built for another goal".to_string());
+        }
+
+        let mut patterns = Vec::new();
+        for _ in 0..reader.u32()? {
+            let mut tiles = Vec::new();
+            for _ in 0..reader.u32()? {
+                tiles.push(reader.i32()?);
+            }
+            let length = reader.u64()? as usize;
+            if length != permutations(goal.len(), tiles.len()) as usize {
+                return Err("Invalid pattern table length".to_string());
+            }
+            let table = Vec::from(reader.take(length)?);
+            patterns.push(Pattern { tiles, table });
+        }
+        if patterns
+            .iter()
+            .map(|pattern| pattern.tiles.len())
+            .collect::<Vec<usize>>()
+            != PatternDatabase::layout(goal.len())
+        {
+            return Err("Database built with another layout".to_string());
+        }
+
+        Ok(PatternDatabase {
+            size,
+            goal: file_goal,
+            patterns,
+        })
+    }
+
+    pub fn save(&self, path: &Path) -> Result<(), String> {
+        fs::write(path, self.to_bytes())
+            .map_err(|err| format!("Failed to write {}: {}", path.display(), err))
+    }
+
+    pub fn load(path: &Path, size: i32, goal: &Board) -> Result<PatternDatabase, String> {
+        let bytes =
+            fs::read(path).map_err(|err| format!("Failed to read {}: {}", path.display(), err))?;
+        PatternDatabase::from_bytes(&bytes, size, goal)
+            .map_err(|err| format!("Invalid pattern database {}: {}", path.display(), err))
+    }
+}
+
+fn find(size: i32, goal: &Board) -> Option<Arc<PatternDatabase>> {
+    let current = CURRENT.with(|current| {
+        current
+            .borrow()
+            .as_ref()
+            .filter(|database| database.matches(size, goal))
+            .cloned()
+    });
+    let database = current.or_else(|| {
+        DATABASES
+            .lock()
+            .unwrap()
+            .iter()
+            .find(|database| database.matches(size, goal))
+            .cloned()
+    })?;
+    CURRENT.with(|current| *current.borrow_mut() = Some(database.clone()));
+    Some(database)
+}
+
+fn register(database: PatternDatabase) -> Arc<PatternDatabase> {
+    let database = Arc::new(database);
+    DATABASES.lock().unwrap().push(database.clone());
+    database
+}
+
+// Find or build the database for the given goal
+pub fn get(size: i32, goal: &Board) -> Arc<PatternDatabase> {
+    if let Some(database) = find(size, goal) {
+        return database;
+    }
+    // Build while holding the lock to not build the same database twice
+    let mut databases = DATABASES.lock().unwrap();
+    if let Some(database) = databases
+        .iter()
+        .find(|database| database.matches(size, goal))
+    {
+        return database.clone();
+    }
+    let database = Arc::new(PatternDatabase::build(size, goal));
+    databases.push(database.clone());
+    database
+}
+
+pub enum Source {
+    Memory,
+    Disk(PathBuf),
+    Built(PathBuf),
+    // The cached file was invalid and has been replaced
+    Rebuilt(PathBuf, String),
+}
+
+// Use the database cached in the directory if there is one,
+// -- or build it and save it for the next runs
+pub fn load_or_build(directory: &Path, size: i32, goal: &Board) -> Result<Source, String> {
+    if find(size, goal).is_some() {
+        return Ok(Source::Memory);
+    }
+    let path = directory.join(PatternDatabase::file_name(size, goal));
+    let mut error = None;
+    if path.exists() {
+        match PatternDatabase::load(&path, size, goal) {
+            Ok(database) => {
+                register(database);
+                return Ok(Source::Disk(path));
+            }
+            Err(err) => error = Some(err),
+        }
+    }
+
+    let database = PatternDatabase::build(size, goal);
+    fs::create_dir_all(directory)
+        .map_err(|err| format!("Failed to create {}: {}", directory.display(), err))?;
+    database.save(&path)?;
+    register(database);
+    match error {
+        Some(err) => Ok(Source::Rebuilt(path, err)),
+        None => Ok(Source::Built(path)),
+    }
+}
+
+#[test]
+fn rank_unrank() {
+    let mut positions = [0; 3];
+    for rank in 0..permutations(9, 3) as usize {
+        Pattern::unrank(9, rank, &mut positions);
+        assert_eq!(Pattern::rank(9, &positions), rank);
+    }
+}
+
+#[test]
+fn layout_sizes() {
+    assert_eq!(PatternDatabase::layout(9), vec![6, 2]);
+    assert_eq!(PatternDatabase::layout(16), vec![6, 6, 3]);
+    assert_eq!(PatternDatabase::layout(25), vec![6, 6, 6, 6]);
+    assert_eq!(PatternDatabase::layout(8), vec![6, 1]);
+}
+
+#[test]
+fn goal_is_zero() {
+    for goal in [
+        Board::from(vec![1, 2, 3, 8, 0, 4, 7, 6, 5]),
+        Board::from(vec![0, 1, 2, 3, 4, 5, 6, 7, 8]),
+        Board::from(vec![1, 2, 3, 4, 5, 6, 7, 8, 0]),
+    ] {
+        assert_eq!(get(3, &goal).evaluate(&goal), 0);
+    }
+}
+
+#[test]
+fn single_move() {
+    let goal = Board::from(vec![1, 2, 3, 8, 0, 4, 7, 6, 5]);
+    let node = Board::from(vec![1, 2, 3, 0, 8, 4, 7, 6, 5]);
+    assert_eq!(get(3, &goal).evaluate(&node), 1);
+}
+
+#[test]
+fn save_and_load() {
+    let goal = Board::from(vec![1, 2, 3, 8, 0, 4, 7, 6, 5]);
+    let database = PatternDatabase::build(3, &goal);
+    let loaded = PatternDatabase::from_bytes(&database.to_bytes(), 3, &goal).unwrap();
+    assert_eq!(loaded.goal, goal);
+    assert_eq!(loaded.patterns.len(), database.patterns.len());
+    for (left, right) in loaded.patterns.iter().zip(database.patterns.iter()) {
+        assert_eq!(left.tiles, right.tiles);
+        assert_eq!(left.table, right.table);
+    }
+}
+
+#[test]
+fn load_other_goal() {
+    let snail = Board::from(vec![1, 2, 3, 8, 0, 4, 7, 6, 5]);
+    let first = Board::from(vec![0, 1, 2, 3, 4, 5, 6, 7, 8]);
+    let bytes = PatternDatabase::build(3, &snail).to_bytes();
+    assert!(PatternDatabase::from_bytes(&bytes, 3, &first).is_err());
+    assert_ne!(
+        PatternDatabase::file_name(3, &snail),
+        PatternDatabase::file_name(3, &first)
+    );
+}
+
+#[test]
+fn load_corrupted() {
+    let goal = Board::from(vec![1, 2, 3, 4, 5, 6, 7, 8, 0]);
+    let mut bytes = PatternDatabase::build(3, &goal).to_bytes();
+    let middle = bytes.len() / 2;
+    bytes[middle] ^= 1;
+    assert!(PatternDatabase::from_bytes(&bytes, 3, &goal).is_err());
+}
@@ -1,12 +1,14 @@
 use crate::{goal, heuristic};
 use core::fmt;
 use rand::prelude::SliceRandom;
-use rand::thread_rng;
+use rand::Rng;
 use std::fs;
 use std::num::ParseIntError;
 
 pub struct Puzzle {
+    // Number of columns, which is also the number of rows for square puzzles
     pub size: i32,
+    pub height: i32,
     pub map: Vec<i32>,
     pub goal: Vec<i32>,
 }
@@ -23,7 +25,7 @@ impl Puzzle {
             if byte == 0 {
                 return Ok(None);
             }
-            line = (&line[0..byte]).trim()
+            line = line[0..byte].trim()
         }
         // Parse each cols
         let cols: Vec<Result<i32, ParseIntError>> = line
@@ -41,8 +43,10 @@ impl Puzzle {
         Ok(Some(clean_cols))
     }
 
-    fn parse_content(content: &str) -> Result<(i32, Vec<i32>), String> {
+    // The first line is the size of a square puzzle, or its number of rows and columns
+    fn parse_content(content: &str) -> Result<(i32, i32, Vec<i32>), String> {
         let mut size: i32 = 0;
+        let mut height: i32 = 0;
         let mut empty_col: bool = false;
         let mut map: Vec<i32> = Vec::new();
 
@@ -53,11 +57,17 @@ impl Puzzle {
                 continue;
             } else if let Some(cols) = parsed_line {
                 if size == 0 {
-                    if cols.len() != 1 {
-                        return Err("Expected only 1 value as the puzzle size on the first line"
-                            .to_string());
+                    if cols.len() != 1 && cols.len() != 2 {
+                        return Err(
+                            "Expected the puzzle size or its rows and columns on the first line"
+                                .to_string(),
+                        );
+                    }
+                    size = *cols.last().unwrap();
+                    height = cols[0];
+                    if size <= 0 || height <= 0 {
+                        return Err(format!("Invalid puzzle size `{}`", line.trim()));
                     }
-                    size = cols[0];
                 } else {
                     if cols.len() != size.try_into().unwrap() {
                         return Err(format!(
@@ -83,7 +93,7 @@ impl Puzzle {
 
         // Check final size, in case of missing or extra lines
         let cell_count: i32 = map.len().try_into().unwrap();
-        let expected_count = size * size;
+        let expected_count = size * height;
         if cell_count != expected_count {
             return Err(format!(
                 "Invalid number of cells `{}`, expected {}",
@@ -91,13 +101,13 @@ impl Puzzle {
             ));
         }
 
-        Ok((size, map))
+        Ok((size, height, map))
     }
 
     // Check that the puzzle has all required valid numbers for the given size
     // -- and an empty cell
-    fn check_validity(size: i32, map: &[i32]) -> Result<(), String> {
-        let max_size = (size * size) - 1;
+    fn check_validity(map: &[i32]) -> Result<(), String> {
+        let max_size = map.len() as i32 - 1;
         let mut goal: Vec<bool> = vec![false; (max_size + 1) as usize];
         for &value in map.iter() {
             if value > max_size || value < 0 {
@@ -116,36 +126,83 @@ impl Puzzle {
         Ok(())
     }
 
-    pub fn new(path: &str, solution_type: &str) -> Result<Puzzle, String> {
+    // Columns, rows and cells of a valid map file
+    pub fn read(path: &str) -> Result<(i32, i32, Vec<i32>), String> {
         let content = fs::read_to_string(path);
         if let Err(e) = content {
             return Err(format!("Failed to open or read puzzle file: {}", e));
         }
 
         let content = content.unwrap();
-        let (size, map) = Puzzle::parse_content(&content)?;
-        Puzzle::check_validity(size, &map)?;
+        let (size, height, map) = Puzzle::parse_content(&content)?;
+        Puzzle::check_validity(&map)?;
+        Ok((size, height, map))
+    }
 
-        let map_goal = goal::generate(size, solution_type)?;
+    pub fn new(path: &str, solution_type: &str) -> Result<Puzzle, String> {
+        let (size, height, map) = Puzzle::read(path)?;
+        let map_goal = goal::generate_rectangle(size, height, solution_type)?;
         Ok(Puzzle {
             size,
+            height,
             map,
             goal: map_goal,
         })
     }
 
-    pub fn generate(solvable: bool, size: i32, solution_type: &str) -> Result<Puzzle, String> {
-        let mut map: Vec<i32> = (0..=(size * size) - 1).collect();
-        let goal = goal::generate(size, solution_type)?;
-        let mut rng = thread_rng();
-        map.shuffle(&mut rng);
+    // Puzzle of a map file solved toward a goal of the same size
+    pub fn with_goal(path: &str, goal: &[i32], goal_size: i32) -> Result<Puzzle, String> {
+        let (size, height, map) = Puzzle::read(path)?;
+        if size != goal_size || map.len() != goal.len() {
+            return Err(format!(
+                "The puzzle is {}x{} but the goal is {}x{}",
+                height,
+                size,
+                goal.len() as i32 / goal_size,
+                goal_size
+            ));
+        }
+        Ok(Puzzle {
+            size,
+            height,
+            map,
+            goal: goal.to_vec(),
+        })
+    }
+
+    pub fn generate(
+        solvable: bool,
+        size: i32,
+        height: i32,
+        solution_type: &str,
+        rng: &mut impl Rng,
+    ) -> Result<Puzzle, String> {
+        let goal = goal::generate_rectangle(size, height, solution_type)?;
+        Ok(Puzzle::generate_for_goal(solvable, size, goal, rng))
+    }
+
+    // Random puzzle with the size of the goal, the same rng state always gives the same puzzle
+    pub fn generate_for_goal(
+        solvable: bool,
+        size: i32,
+        goal: Vec<i32>,
+        rng: &mut impl Rng,
+    ) -> Puzzle {
+        let height = goal.len() as i32 / size;
+        let mut map: Vec<i32> = (0..=(size * height) - 1).collect();
+        map.shuffle(rng);
         // Ouch
         while (solvable && !Puzzle::is_map_solvable(size, &map, &goal))
             || (!solvable && Puzzle::is_map_solvable(size, &map, &goal))
         {
-            map.shuffle(&mut rng);
+            map.shuffle(rng);
+        }
+        Puzzle {
+            size,
+            height,
+            map,
+            goal,
         }
-        Ok(Puzzle { size, map, goal })
     }
 
     fn is_map_solvable(size: i32, map: &[i32], goal: &[i32]) -> bool {
@@ -190,3 +247,63 @@ impl fmt::Display for Puzzle {
         res
     }
 }
+
+// Puzzles of the snail goal and the length of their optimal solution, shared by the solver tests
+#[cfg(test)]
+pub const OPTIMAL_LENGTHS: [(&str, usize); 3] = [
+    ("puzzles/solv_3_1.map", 26),
+    ("puzzles/solv_3_3.map", 20),
+    ("puzzles/solv_3_4.map", 30),
+];
+
+// Check that the solution of each of them goes from the puzzle to its goal by sliding
+// -- one tile at a time, and is at most bound times longer than the optimal solution
+#[cfg(test)]
+pub fn check_solutions(bound: usize, mut solve: impl FnMut(&Puzzle) -> npuzzle::Solution) {
+    for (path, optimal) in OPTIMAL_LENGTHS {
+        let puzzle = Puzzle::new(path, "snail").unwrap();
+        let solution = solve(&puzzle);
+        let length = solution.steps.len() - 1;
+        if bound == 1 {
+            assert_eq!(length, optimal, "{}", path);
+        } else {
+            assert!(length <= optimal * bound, "{}", path);
+        }
+        assert_eq!(solution.steps[0], puzzle.map);
+        assert_eq!(*solution.steps.last().unwrap(), puzzle.goal);
+        for pair in solution.steps.windows(2) {
+            let next = npuzzle::Board::from(&pair[1][..]);
+            assert!(
+                npuzzle::neighbors(puzzle.size, &npuzzle::Board::from(&pair[0][..]))
+                    .into_iter()
+                    .flatten()
+                    .any(|neighbor| neighbor == next),
+                "{}",
+                path
+            );
+        }
+        assert_eq!(
+            npuzzle::blank_moves(puzzle.size, &solution.steps),
+            Ok(solution.moves),
+            "{}",
+            path
+        );
+    }
+}
+
+#[test]
+fn seeded_generation() {
+    use rand::rngs::StdRng;
+    use rand::SeedableRng;
+    let batch = |seed: u64| -> Vec<Vec<i32>> {
+        let mut rng = StdRng::seed_from_u64(seed);
+        (0..3)
+            .map(|_| Puzzle::generate(true, 4, 3, "snail", &mut rng).unwrap().map)
+            .collect()
+    };
+    assert_eq!(batch(42), batch(42));
+    assert_ne!(batch(42), batch(43));
+    // Each puzzle of a batch continues from the previous one
+    let first = batch(42);
+    assert_ne!(first[0], first[1]);
+}
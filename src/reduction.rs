@@ -0,0 +1,486 @@
+use crate::config::Config;
+use crate::ida_star;
+use crate::puzzle::Puzzle;
+use npuzzle::{blank_moves, Heuristic, Solution};
+use std::collections::hash_map::Entry;
+use std::collections::{HashMap, VecDeque};
+
+// Biggest side of the remainder solved optimally once every other line is placed
+const REMAINDER: usize = 3;
+
+// Cells of the board that are not placed yet, inclusive bounds
+#[derive(Clone, Copy)]
+struct Region {
+    left: usize,
+    top: usize,
+    right: usize,
+    bottom: usize,
+}
+
+impl Region {
+    fn width(&self) -> usize {
+        self.right - self.left + 1
+    }
+
+    fn height(&self) -> usize {
+        self.bottom - self.top + 1
+    }
+}
+
+struct Reducer<'a> {
+    size: usize,
+    height: usize,
+    board: Vec<i32>,
+    goal: &'a [i32],
+    // Cells whose tile is placed and must not move anymore
+    locked: Vec<bool>,
+    steps: Vec<Vec<i32>>,
+    // Generation of the last search that reached each state, to not clear them for each search
+    visited: Vec<u32>,
+    parent: Vec<u32>,
+    generation: u32,
+    total_used_states: usize,
+    // Longest queue of the searches
+    biggest_state: usize,
+}
+
+impl Reducer<'_> {
+    fn blank(&self) -> usize {
+        self.board.iter().position(|&cell| cell == 0).unwrap()
+    }
+
+    fn adjacent(&self, cell: usize) -> impl Iterator<Item = usize> + '_ {
+        let (x, y) = (cell % self.size, cell / self.size);
+        [
+            (x > 0).then(|| cell - 1),
+            (x + 1 < self.size).then(|| cell + 1),
+            (y > 0).then(|| cell - self.size),
+            (y + 1 < self.height).then(|| cell + self.size),
+        ]
+        .into_iter()
+        .flatten()
+        .filter(|&next| !self.locked[next])
+    }
+
+    // Move the tile at cell, next to the empty cell, to the empty cell
+    fn slide(&mut self, cell: usize) {
+        let blank = self.blank();
+        self.board.swap(blank, cell);
+        self.steps.push(self.board.clone());
+    }
+
+    fn next_generation(&mut self) -> u32 {
+        self.generation += 1;
+        self.generation
+    }
+
+    // Shortest path between two cells that does not go through the avoided cell, without the start
+    fn path(&mut self, start: usize, target: usize, avoid: Option<usize>) -> Option<Vec<usize>> {
+        let generation = self.next_generation();
+        self.visited[start] = generation;
+        let mut queue = VecDeque::from([start]);
+        while let Some(cell) = queue.pop_front() {
+            if cell == target {
+                break;
+            }
+            self.total_used_states += 1;
+            let next: Vec<usize> = self
+                .adjacent(cell)
+                .filter(|&next| Some(next) != avoid)
+                .collect();
+            for next in next {
+                if self.visited[next] != generation {
+                    self.visited[next] = generation;
+                    self.parent[next] = cell as u32;
+                    queue.push_back(next);
+                }
+            }
+            self.biggest_state = self.biggest_state.max(queue.len());
+        }
+        if self.visited[target] != generation {
+            return None;
+        }
+        let mut path = vec![];
+        let mut cell = target;
+        while cell != start {
+            path.push(cell);
+            cell = self.parent[cell] as usize;
+        }
+        path.reverse();
+        Some(path)
+    }
+
+    fn move_blank(&mut self, target: usize) -> Result<(), String> {
+        let path = self
+            .path(self.blank(), target, None)
+            .ok_or_else(|| String::from("The empty cell is stuck between placed tiles"))?;
+        for cell in path {
+            self.slide(cell);
+        }
+        Ok(())
+    }
+
+    // Walk the tile along its shortest path, bringing the empty cell in front of it at each step,
+    // -- or search every position of the tile and the empty cell if the empty cell can't go around it
+    fn move_tile(&mut self, tile: i32, target: usize) -> Result<(), String> {
+        let mut position = self.board.iter().position(|&cell| cell == tile).unwrap();
+        let path = self
+            .path(position, target, None)
+            .ok_or_else(|| format!("Tile {} can't reach its cell {}", tile, target))?;
+        for next in path {
+            match self.path(self.blank(), next, Some(position)) {
+                Some(blank_path) => {
+                    for cell in blank_path {
+                        self.slide(cell);
+                    }
+                }
+                None => return self.search_tile(tile, target),
+            }
+            self.slide(position);
+            position = next;
+        }
+        Ok(())
+    }
+
+    // Shortest sequence of moves of the empty cell bringing the tile to the target,
+    // -- each state is the cell of the tile and the cell of the empty cell
+    fn search_tile(&mut self, tile: i32, target: usize) -> Result<(), String> {
+        let cells = self.size * self.height;
+        let generation = self.next_generation();
+        let position = self.board.iter().position(|&cell| cell == tile).unwrap();
+        let start = position * cells + self.blank();
+        self.visited[start] = generation;
+        let mut queue = VecDeque::from([start]);
+        let mut found = None;
+        while let Some(state) = queue.pop_front() {
+            let (tile_cell, blank) = (state / cells, state % cells);
+            if tile_cell == target {
+                found = Some(state);
+                break;
+            }
+            self.total_used_states += 1;
+            let next: Vec<usize> = self.adjacent(blank).collect();
+            for next in next {
+                let next_state = if next == tile_cell {
+                    blank * cells + next
+                } else {
+                    tile_cell * cells + next
+                };
+                if self.visited[next_state] != generation {
+                    self.visited[next_state] = generation;
+                    self.parent[next_state] = state as u32;
+                    queue.push_back(next_state);
+                }
+            }
+            self.biggest_state = self.biggest_state.max(queue.len());
+        }
+        let mut state =
+            found.ok_or_else(|| format!("Tile {} can't reach its cell {}", tile, target))?;
+        let mut path = vec![];
+        while state != start {
+            path.push(state % cells);
+            state = self.parent[state] as usize;
+        }
+        for &cell in path.iter().rev() {
+            self.slide(cell);
+        }
+        Ok(())
+    }
+
+    // Shortest sequence of moves of the empty cell placing the goal tiles of two cells at once,
+    // -- each state is the cell of both tiles and the cell of the empty cell
+    fn search_pair(&mut self, first: usize, second: usize) -> Result<(), String> {
+        let cells = self.size * self.height;
+        let (tile_first, tile_second) = (self.goal[first], self.goal[second]);
+        let position = |tile: i32| self.board.iter().position(|&cell| cell == tile).unwrap();
+        let start = (position(tile_first) * cells + position(tile_second)) * cells + self.blank();
+        let mut parent = HashMap::from([(start, start)]);
+        let mut queue = VecDeque::from([start]);
+        let mut found = None;
+        while let Some(state) = queue.pop_front() {
+            let (a, b, blank) = (state / cells / cells, state / cells % cells, state % cells);
+            if a == first && b == second {
+                found = Some(state);
+                break;
+            }
+            self.total_used_states += 1;
+            for next in self.adjacent(blank) {
+                let (a, b) = match next {
+                    _ if next == a => (blank, b),
+                    _ if next == b => (a, blank),
+                    _ => (a, b),
+                };
+                let next_state = (a * cells + b) * cells + next;
+                if let Entry::Vacant(entry) = parent.entry(next_state) {
+                    entry.insert(state);
+                    queue.push_back(next_state);
+                }
+            }
+            self.biggest_state = self.biggest_state.max(queue.len());
+        }
+        let mut state = found.ok_or_else(|| {
+            format!(
+                "Tiles {} and {} can't reach their cells {} and {}",
+                tile_first, tile_second, first, second
+            )
+        })?;
+        let mut path = vec![];
+        while state != start {
+            path.push(state % cells);
+            state = parent[&state];
+        }
+        for &cell in path.iter().rev() {
+            self.slide(cell);
+        }
+        Ok(())
+    }
+
+    // Place the goal tiles of a line one by one and lock them,
+    // -- the last tile can't be moved to its cell without moving the one before it,
+    // -- so the last tile is placed on the cell before and the one before it next to it toward the inside,
+    // -- then both are rotated into place
+    fn place_line(&mut self, cells: &[usize], inside: &[usize]) -> Result<(), String> {
+        let last = cells.len() - 1;
+        for &cell in cells[..last - 1].iter() {
+            self.move_tile(self.goal[cell], cell)?;
+            self.locked[cell] = true;
+        }
+        let (before, after) = (cells[last - 1], cells[last]);
+        if cells.len() == 2 {
+            // The region is only two cells wide, there is no room to keep a tile away
+            self.search_pair(before, after)?;
+        } else if self.board[before] != self.goal[before] || self.board[after] != self.goal[after] {
+            // The tile of the cell before is kept away from the last cell while the last tile moves,
+            // -- since it would be stuck in the corner once the cell before is locked
+            let away = 2 * inside[last - 1] - before;
+            self.move_tile(self.goal[before], away)?;
+            self.locked[away] = true;
+            self.move_tile(self.goal[after], before)?;
+            self.locked[before] = true;
+            self.locked[away] = false;
+            self.move_tile(self.goal[before], inside[last - 1])?;
+            self.locked[inside[last - 1]] = true;
+            self.move_blank(after)?;
+            self.locked[before] = false;
+            self.locked[inside[last - 1]] = false;
+            self.slide(before);
+            self.slide(inside[last - 1]);
+        }
+        self.locked[before] = true;
+        self.locked[after] = true;
+        Ok(())
+    }
+
+    // Place the lines of the region on the side where the empty cell does not end,
+    // -- along the longest side first so the region stays close to a square, which solves the snail ring by ring
+    fn reduce(&mut self, region: &mut Region) -> Result<(), String> {
+        let goal_blank = self.goal.iter().position(|&cell| cell == 0).unwrap();
+        while let Some((cells, inside)) = next_line(region, self.size, goal_blank) {
+            self.place_line(&cells, &inside)?;
+        }
+        Ok(())
+    }
+}
+
+// Remove the next line to place from the region, away from the goal of the empty cell,
+// -- returns its cells and the cells next to it inside the region, or None once the region
+// -- is small enough for IDA*
+fn next_line(
+    region: &mut Region,
+    size: usize,
+    goal_blank: usize,
+) -> Option<(Vec<usize>, Vec<usize>)> {
+    let (blank_x, blank_y) = (goal_blank % size, goal_blank / size);
+    if region.width() <= REMAINDER && region.height() <= REMAINDER {
+        return None;
+    }
+    if region.height() > REMAINDER
+        && (region.height() >= region.width() || region.width() <= REMAINDER)
+    {
+        let (row, inside_row) = if blank_y != region.top {
+            region.top += 1;
+            (region.top - 1, region.top)
+        } else {
+            region.bottom -= 1;
+            (region.bottom + 1, region.bottom)
+        };
+        let cells = (region.left..=region.right)
+            .map(|x| row * size + x)
+            .collect();
+        let inside = (region.left..=region.right)
+            .map(|x| inside_row * size + x)
+            .collect();
+        Some((cells, inside))
+    } else {
+        let (column, inside_column) = if blank_x != region.left {
+            region.left += 1;
+            (region.left - 1, region.left)
+        } else {
+            region.right -= 1;
+            (region.right + 1, region.right)
+        };
+        let cells = (region.top..=region.bottom)
+            .map(|y| y * size + column)
+            .collect();
+        let inside = (region.top..=region.bottom)
+            .map(|y| y * size + inside_column)
+            .collect();
+        Some((cells, inside))
+    }
+}
+
+// Cells of the region and the tiles of its goal, which are named by their goal cell in the region
+fn remainder(size: usize, region: &Region, goal: &[i32]) -> (Vec<usize>, Vec<i32>) {
+    let cells: Vec<usize> = (region.top..=region.bottom)
+        .flat_map(|y| (region.left..=region.right).map(move |x| y * size + x))
+        .collect();
+    let tiles = cells
+        .iter()
+        .map(|&cell| goal[cell])
+        .filter(|&tile| tile != 0)
+        .collect();
+    (cells, tiles)
+}
+
+fn local(tiles: &[i32], tile: i32) -> i32 {
+    match tile {
+        0 => 0,
+        _ => tiles.iter().position(|&other| other == tile).unwrap() as i32 + 1,
+    }
+}
+
+// Columns and goal of the region left to IDA*, they only depend on the goal
+// -- so the pattern database of the region can be built before solving
+pub fn remainder_goal(puzzle: &Puzzle) -> (i32, Vec<i32>) {
+    let size = puzzle.size as usize;
+    let mut region = Region {
+        left: 0,
+        top: 0,
+        right: size - 1,
+        bottom: puzzle.height as usize - 1,
+    };
+    let goal_blank = puzzle.goal.iter().position(|&cell| cell == 0).unwrap();
+    while next_line(&mut region, size, goal_blank).is_some() {}
+    let (cells, tiles) = remainder(size, &region, &puzzle.goal);
+    let goal = cells
+        .iter()
+        .map(|&cell| local(&tiles, puzzle.goal[cell]))
+        .collect();
+    (region.width() as i32, goal)
+}
+
+// Place the tiles line by line toward the goal of the empty cell until at most a 3x3 region is left,
+// -- which is solved with IDA*, the solution is fast to find but is not optimal
+pub fn solve(puzzle: &Puzzle, config: &Config, heuristic: Heuristic) -> Result<Solution, String> {
+    let size = puzzle.size as usize;
+    let height = puzzle.height as usize;
+    let cells = size * height;
+    let mut reducer = Reducer {
+        size,
+        height,
+        board: puzzle.map.clone(),
+        goal: &puzzle.goal,
+        locked: vec![false; cells],
+        steps: vec![puzzle.map.clone()],
+        visited: vec![0; cells * cells],
+        parent: vec![0; cells * cells],
+        generation: 0,
+        total_used_states: 0,
+        biggest_state: 1,
+    };
+    let mut region = Region {
+        left: 0,
+        top: 0,
+        right: size - 1,
+        bottom: height - 1,
+    };
+    reducer.reduce(&mut region)?;
+
+    let (remainder, tiles) = remainder(size, &region, &puzzle.goal);
+    let local = |tile: i32| local(&tiles, tile);
+    let sub_puzzle = Puzzle {
+        size: region.width() as i32,
+        height: region.height() as i32,
+        map: remainder
+            .iter()
+            .map(|&cell| local(reducer.board[cell]))
+            .collect(),
+        goal: remainder
+            .iter()
+            .map(|&cell| local(puzzle.goal[cell]))
+            .collect(),
+    };
+    let sub_solution = ida_star::solve(&sub_puzzle, config, heuristic)?;
+    for step in sub_solution.steps.iter().skip(1) {
+        let mut board = reducer.board.clone();
+        for (&cell, &value) in remainder.iter().zip(step.iter()) {
+            board[cell] = match value {
+                0 => 0,
+                _ => tiles[value as usize - 1],
+            };
+        }
+        reducer.steps.push(board);
+    }
+
+    Ok(Solution {
+        total_used_states: reducer.total_used_states + sub_solution.total_used_states,
+        biggest_state: reducer.biggest_state.max(sub_solution.biggest_state),
+        moves: blank_moves(puzzle.size, &reducer.steps)?,
+        steps: reducer.steps,
+        expanded_by_direction: None,
+        suboptimality_bound: None,
+        transposition_hits: None,
+        layer_histogram: None,
+    })
+}
+
+#[test]
+fn valid_moves_to_goal() {
+    use rand::rngs::StdRng;
+    use rand::SeedableRng;
+    let mut rng = StdRng::seed_from_u64(18);
+    // Columns and rows of each puzzle
+    for (size, height) in [
+        (3, 3),
+        (4, 4),
+        (5, 5),
+        (7, 7),
+        (10, 10),
+        (5, 2),
+        (2, 6),
+        (7, 4),
+    ] {
+        for solution_type in ["snail", "first", "last"] {
+            let puzzle = Puzzle::generate(true, size, height, solution_type, &mut rng).unwrap();
+            let solution = solve(&puzzle, &Config::default(), crate::heuristic::MANHATTAN).unwrap();
+            assert_eq!(solution.steps[0], puzzle.map);
+            assert_eq!(*solution.steps.last().unwrap(), puzzle.goal);
+            for pair in solution.steps.windows(2) {
+                let moved: Vec<usize> = (0..pair[0].len())
+                    .filter(|&cell| pair[0][cell] != pair[1][cell])
+                    .collect();
+                assert_eq!(moved.len(), 2, "{}x{} {}", height, size, solution_type);
+                let (from, to) = (moved[0] as i32, moved[1] as i32);
+                assert!(to - from == 1 && from / size == to / size || to - from == size);
+            }
+        }
+    }
+}
+
+#[test]
+fn remainder_goal_fits_ida_star() {
+    use rand::rngs::StdRng;
+    use rand::SeedableRng;
+    let mut rng = StdRng::seed_from_u64(18);
+    for (size, height) in [(5, 5), (7, 4), (2, 6)] {
+        for solution_type in ["snail", "first", "last"] {
+            let puzzle = Puzzle::generate(true, size, height, solution_type, &mut rng).unwrap();
+            let (width, goal) = remainder_goal(&puzzle);
+            assert!(width as usize <= REMAINDER);
+            assert!(goal.len() / width as usize <= REMAINDER);
+            let mut tiles = goal.clone();
+            tiles.sort();
+            assert_eq!(tiles, (0..goal.len() as i32).collect::<Vec<i32>>());
+        }
+    }
+}
@@ -0,0 +1,333 @@
+use crate::config::Config;
+use crate::puzzle::Puzzle;
+use npuzzle::{blank_moves, neighbors, Board, Heuristic, Solution};
+use std::cmp::Reverse;
+use std::collections::BTreeSet;
+use std::time::Instant;
+
+// Amount of nodes kept in memory if no --max-nodes is given
+pub const DEFAULT_MAX_NODES: usize = 1_000_000;
+
+// Costs are never negative so their bits are ordered like them
+type Key = (u64, Reverse<usize>, usize);
+
+struct Node {
+    board: Board,
+    parent: Option<usize>,
+    depth: usize,
+    g: f64,
+    h: f64,
+    // Best cost of a solution through this node, backed up from its children
+    f: f64,
+    // Every neighbor except the parent
+    successors: Vec<Board>,
+    // Node in memory for each successor
+    children: Vec<Option<usize>>,
+    // Cost of each successor when it was forgotten
+    forgotten: Vec<Option<f64>>,
+}
+
+impl Node {
+    // Each successor was generated at least once
+    fn is_complete(&self) -> bool {
+        self.children
+            .iter()
+            .zip(self.forgotten.iter())
+            .all(|(child, forgotten)| child.is_some() || forgotten.is_some())
+    }
+
+    fn is_leaf(&self) -> bool {
+        self.children.iter().all(Option::is_none)
+    }
+
+    // Some successors are not in memory and can be generated
+    fn is_open(&self) -> bool {
+        self.children.iter().any(Option::is_none)
+    }
+}
+
+struct Memory {
+    nodes: Vec<Option<Node>>,
+    free: Vec<usize>,
+    used: usize,
+    // Lowest cost first, and the deepest for the same cost
+    open_set: BTreeSet<Key>,
+    // The last is the highest cost, and the shallowest for the same cost
+    leaves: BTreeSet<Key>,
+}
+
+impl Memory {
+    fn node(&self, id: usize) -> &Node {
+        self.nodes[id].as_ref().unwrap()
+    }
+
+    fn node_mut(&mut self, id: usize) -> &mut Node {
+        self.nodes[id].as_mut().unwrap()
+    }
+
+    fn key(&self, id: usize) -> Key {
+        let node = self.node(id);
+        (node.f.to_bits(), Reverse(node.depth), id)
+    }
+
+    // Remove the node from the sets before its cost or children are updated
+    fn detach(&mut self, id: usize) {
+        let key = self.key(id);
+        self.open_set.remove(&key);
+        self.leaves.remove(&key);
+    }
+
+    fn attach(&mut self, id: usize) {
+        let key = self.key(id);
+        let (is_open, is_leaf) = (self.node(id).is_open(), self.node(id).is_leaf());
+        if is_open {
+            self.open_set.insert(key);
+        }
+        if is_leaf {
+            self.leaves.insert(key);
+        }
+    }
+
+    fn insert(&mut self, node: Node) -> usize {
+        self.used += 1;
+        let id = match self.free.pop() {
+            Some(id) => {
+                self.nodes[id] = Some(node);
+                id
+            }
+            None => {
+                self.nodes.push(Some(node));
+                self.nodes.len() - 1
+            }
+        };
+        self.attach(id);
+        id
+    }
+
+    // Once every successor is known, the cost of a node is the lowest cost of its successors
+    fn backup(&mut self, mut id: usize) {
+        loop {
+            let node = self.node(id);
+            if !node.is_complete() {
+                return;
+            }
+            let f = node
+                .children
+                .iter()
+                .zip(node.forgotten.iter())
+                .map(|(child, forgotten)| match child {
+                    Some(child) => self.node(*child).f,
+                    None => forgotten.unwrap(),
+                })
+                .fold(f64::INFINITY, f64::min);
+            if f == node.f {
+                return;
+            }
+            let parent = node.parent;
+            self.detach(id);
+            self.node_mut(id).f = f;
+            self.attach(id);
+            match parent {
+                Some(parent) => id = parent,
+                None => return,
+            }
+        }
+    }
+
+    // Remove the shallowest leaf with the highest cost and keep its cost in its parent,
+    // -- returns false if only the protected nodes could be forgotten
+    fn forget(&mut self, protected: &[usize]) -> bool {
+        let worst = self
+            .leaves
+            .iter()
+            .rev()
+            .map(|key| key.2)
+            .find(|id| !protected.contains(id) && self.node(*id).parent.is_some());
+        let id = match worst {
+            Some(id) => id,
+            None => return false,
+        };
+        self.detach(id);
+        let node = self.nodes[id].take().unwrap();
+        self.free.push(id);
+        self.used -= 1;
+
+        let parent = node.parent.unwrap();
+        self.detach(parent);
+        let parent_node = self.node_mut(parent);
+        let index = parent_node
+            .children
+            .iter()
+            .position(|child| *child == Some(id))
+            .unwrap();
+        parent_node.children[index] = None;
+        parent_node.forgotten[index] = Some(node.f);
+        self.attach(parent);
+        self.backup(parent);
+        true
+    }
+
+    fn path_to(&self, mut id: usize) -> Vec<Vec<i32>> {
+        let mut full_path = vec![self.node(id).board.to_vec()];
+        while let Some(parent) = self.node(id).parent {
+            full_path.push(self.node(parent).board.to_vec());
+            id = parent;
+        }
+        full_path.reverse();
+        full_path
+    }
+}
+
+fn successors(size: i32, board: &Board, parent: Option<&Board>) -> Vec<Board> {
+    neighbors(size, board)
+        .into_iter()
+        .flatten()
+        .filter(|neighbor| Some(neighbor) != parent)
+        .collect()
+}
+
+// Simplified memory-bounded A*, which forgets the worst leaves when the memory is full,
+// -- their parent keeps their cost to generate them again only if nothing is better
+pub fn solve(puzzle: &Puzzle, config: &Config, heuristic: Heuristic) -> Result<Solution, String> {
+    let now = Instant::now();
+    let mode = config.mode();
+    let max_nodes = config.max_nodes.unwrap_or(DEFAULT_MAX_NODES);
+    let map = Board::from(&puzzle.map[..]);
+    let goal = Board::from(&puzzle.goal[..]);
+
+    // Summary
+    let mut total_used_states = 0;
+    let mut biggest_state: usize = 1; // 1 is the initial state
+
+    // State
+    let mut memory = Memory {
+        nodes: vec![],
+        free: vec![],
+        used: 0,
+        open_set: BTreeSet::new(),
+        leaves: BTreeSet::new(),
+    };
+    let h = heuristic.evaluate(puzzle.size, &map, &goal);
+    let successors_of_map = successors(puzzle.size, &map, None);
+    memory.insert(Node {
+        board: map.clone(),
+        parent: None,
+        depth: 0,
+        g: 0.,
+        h,
+        f: mode.cost(0., h),
+        children: vec![None; successors_of_map.len()],
+        forgotten: vec![None; successors_of_map.len()],
+        successors: successors_of_map,
+    });
+
+    while let Some(&(_, _, best)) = memory.open_set.first() {
+        let node = memory.node(best);
+        if node.board == goal {
+            let steps = memory.path_to(best);
+            return Ok(Solution {
+                total_used_states,
+                biggest_state,
+                moves: blank_moves(puzzle.size, &steps)?,
+                steps,
+                expanded_by_direction: None,
+                suboptimality_bound: mode.suboptimality_bound(&heuristic),
+                transposition_hits: None,
+                layer_histogram: None,
+            });
+        }
+        if node.f == f64::INFINITY {
+            break;
+        }
+
+        // Generate the successor that is not in memory with the lowest known cost
+        let index = (0..node.successors.len())
+            .filter(|index| node.children[*index].is_none())
+            .min_by(|a, b| {
+                let a = node.forgotten[*a].unwrap_or(f64::NEG_INFINITY);
+                let b = node.forgotten[*b].unwrap_or(f64::NEG_INFINITY);
+                a.partial_cmp(&b).unwrap()
+            })
+            .unwrap();
+        let board = node.successors[index].clone();
+        let g = node.g + 1.;
+        let h = heuristic.update(puzzle.size, node.h, &node.board, &board, &goal);
+        let depth = node.depth + 1;
+        // A deeper goal could not be reached with the nodes on its path in memory
+        let f = if depth + 1 >= max_nodes && board != goal {
+            f64::INFINITY
+        } else {
+            mode.cost(g, h)
+                .max(node.f)
+                .max(node.forgotten[index].unwrap_or(0.))
+        };
+        let child_successors = successors(puzzle.size, &board, Some(&node.board));
+        memory.detach(best);
+        let child = memory.insert(Node {
+            board,
+            parent: Some(best),
+            depth,
+            g,
+            h,
+            f,
+            children: vec![None; child_successors.len()],
+            forgotten: vec![None; child_successors.len()],
+            successors: child_successors,
+        });
+        let best_node = memory.node_mut(best);
+        best_node.children[index] = Some(child);
+        best_node.forgotten[index] = None;
+        memory.attach(best);
+        memory.backup(best);
+        memory.backup(child);
+        total_used_states += 1;
+
+        if memory.used > max_nodes && !memory.forget(&[best, child]) {
+            return Err(String::from(
+                "Not enough nodes in memory to find a solution, try a bigger --max-nodes",
+            ));
+        }
+        if memory.used > biggest_state {
+            biggest_state = memory.used;
+        }
+
+        if total_used_states % 100000 == 0 {
+            config.progress(&format!(
+                "#> Explored {} states in {:.2?}",
+                total_used_states,
+                now.elapsed()
+            ));
+        }
+    }
+
+    Err(String::from(
+        "Failed to find a solution for this puzzle with this amount of nodes in memory",
+    ))
+}
+
+#[test]
+fn optimal_with_few_nodes() {
+    for max_nodes in [None, Some(200)] {
+        let config = Config {
+            max_nodes,
+            ..Config::default()
+        };
+        crate::puzzle::check_solutions(1, |puzzle| {
+            let solution = solve(puzzle, &config, crate::heuristic::MANHATTAN).unwrap();
+            if let Some(max_nodes) = max_nodes {
+                assert!(solution.biggest_state <= max_nodes);
+            }
+            solution
+        });
+    }
+}
+
+#[test]
+fn not_enough_nodes() {
+    let config = Config {
+        max_nodes: Some(10),
+        ..Config::default()
+    };
+    let puzzle = Puzzle::new("puzzles/solv_3_1.map", "snail").unwrap();
+    assert!(solve(&puzzle, &config, crate::heuristic::MANHATTAN).is_err());
+}
@@ -0,0 +1,99 @@
+use std::sync::atomic::{AtomicU64, Ordering};
+
+// Each entry is packed in a single u64 so threads can share the table without locks,
+// -- 40 bits of the key, 12 bits for the depth and 12 bits for the iteration
+const DEPTH_BITS: u32 = 12;
+const ITERATION_BITS: u32 = 12;
+const MAX_DEPTH: usize = (1 << DEPTH_BITS) - 1;
+const MAX_ITERATION: u64 = (1 << ITERATION_BITS) - 1;
+
+// Lowest depth at which each node was reached during the current iteration of IDA*,
+// -- entries of previous iterations are ignored and replaced
+pub struct TranspositionTable {
+    entries: Vec<AtomicU64>,
+    iteration: AtomicU64,
+}
+
+impl TranspositionTable {
+    pub fn new(bytes: usize) -> TranspositionTable {
+        let len = (bytes / std::mem::size_of::<AtomicU64>()).max(1);
+        TranspositionTable {
+            entries: (0..len).map(|_| AtomicU64::new(0)).collect(),
+            iteration: AtomicU64::new(1),
+        }
+    }
+
+    // Start a new iteration, entries are only cleared when the iteration counter wraps
+    pub fn next_iteration(&self) {
+        if self.iteration.fetch_add(1, Ordering::Relaxed) + 1 > MAX_ITERATION {
+            for entry in self.entries.iter() {
+                entry.store(0, Ordering::Relaxed);
+            }
+            self.iteration.store(1, Ordering::Relaxed);
+        }
+    }
+
+    // Return true if the key was already reached at the same or a lower depth in this iteration,
+    // -- or else save it at this depth
+    pub fn probe(&self, key: u64, depth: usize) -> bool {
+        if depth > MAX_DEPTH {
+            return false;
+        }
+        let slot = &self.entries[(key % self.entries.len() as u64) as usize];
+        let iteration = self.iteration.load(Ordering::Relaxed);
+        let tag = key >> (DEPTH_BITS + ITERATION_BITS);
+        let current = slot.load(Ordering::Relaxed);
+        let current_tag = current >> (DEPTH_BITS + ITERATION_BITS);
+        let current_depth = ((current >> ITERATION_BITS) as usize) & MAX_DEPTH;
+        let current_iteration = current & MAX_ITERATION;
+        if current_tag == tag && current_iteration == iteration && current_depth <= depth {
+            return true;
+        }
+        let entry =
+            (tag << (DEPTH_BITS + ITERATION_BITS)) | ((depth as u64) << ITERATION_BITS) | iteration;
+        slot.store(entry, Ordering::Relaxed);
+        false
+    }
+}
+
+// Parse a size like 256MB, 1GB or 512KB
+pub fn parse_size(value: &str) -> Result<usize, String> {
+    let value = value.trim().to_uppercase();
+    let (number, unit) = match value.find(|c: char| !c.is_ascii_digit()) {
+        Some(index) => value.split_at(index),
+        None => (value.as_str(), "B"),
+    };
+    let multiplier = match unit {
+        "B" => 1,
+        "KB" => 1 << 10,
+        "MB" => 1 << 20,
+        "GB" => 1 << 30,
+        _ => return Err(format!("unknown unit `{}`", unit)),
+    };
+    let number: usize = number.parse().map_err(|err| format!("{}", err))?;
+    number
+        .checked_mul(multiplier)
+        .ok_or_else(|| String::from("too big for this platform"))
+}
+
+#[test]
+fn lower_depth_only() {
+    let table = TranspositionTable::new(1 << 10);
+    let key = 0xdead_beef_1234_5678;
+    assert!(!table.probe(key, 5));
+    assert!(table.probe(key, 5));
+    assert!(table.probe(key, 7));
+    assert!(!table.probe(key, 3));
+    table.next_iteration();
+    assert!(!table.probe(key, 7));
+}
+
+#[test]
+fn sizes() {
+    assert_eq!(parse_size("256MB"), Ok(256 << 20));
+    assert_eq!(parse_size("1gb"), Ok(1 << 30));
+    assert_eq!(parse_size("4096"), Ok(4096));
+    assert!(parse_size("12TB").is_err());
+    assert!(parse_size("MB").is_err());
+    assert!(parse_size(&format!("{}GB", usize::MAX >> 20)).is_err());
+}
@@ -0,0 +1,129 @@
+use crate::puzzle::Puzzle;
+use npuzzle::{neighbors, parse_blank_moves, parse_tile_moves, Board, Direction};
+
+// A move of the empty cell, or the number of the tile moved into it
+#[derive(Debug, PartialEq)]
+pub enum Move {
+    Blank(Direction),
+    Tile(i32),
+}
+
+pub struct Replay {
+    // Moves applied before the end of the sequence or the first illegal move
+    pub applied: usize,
+    pub board: Vec<i32>,
+    // Position and description of the first illegal move
+    pub illegal: Option<(usize, String)>,
+    pub reached_goal: bool,
+}
+
+// Moves of the empty cell given as letters, like `UURD`, or as the number of each moved tile
+// -- which are only checked against the board while replaying them
+pub fn parse_moves(text: &str) -> Result<Vec<Move>, String> {
+    if let Ok(moves) = parse_blank_moves(text) {
+        return Ok(moves.into_iter().map(Move::Blank).collect());
+    }
+    let tiles = parse_tile_moves(text)
+        .map_err(|err| format!("Moves are neither letters nor tile numbers: {}", err))?;
+    Ok(tiles.into_iter().map(Move::Tile).collect())
+}
+
+// Apply each move, a move is legal only if it leads to one of the neighbors
+pub fn replay(puzzle: &Puzzle, moves: &[Move]) -> Replay {
+    let size = puzzle.size as usize;
+    let mut board = Board::from(&puzzle.map[..]);
+    let mut illegal = None;
+    let mut applied = 0;
+    for (index, step) in moves.iter().enumerate() {
+        let blank = board.blank();
+        let next =
+            neighbors(puzzle.size, &board)
+                .into_iter()
+                .flatten()
+                .find(|neighbor| match step {
+                    Move::Blank(direction) => {
+                        let target = match direction {
+                            Direction::Up => blank.checked_sub(size),
+                            Direction::Down => Some(blank + size),
+                            Direction::Left => blank.checked_sub(1),
+                            Direction::Right => Some(blank + 1),
+                        };
+                        Some(neighbor.blank()) == target
+                    }
+                    // The tile ends up where the empty cell was
+                    Move::Tile(tile) => *tile != 0 && neighbor.get(blank) == *tile,
+                });
+        match next {
+            Some(next) => {
+                board = next;
+                applied += 1;
+            }
+            None => {
+                let err = match step {
+                    Move::Blank(direction) => format!(
+                        "Move {} `{}` takes the empty cell out of the board",
+                        index + 1,
+                        direction.letter()
+                    ),
+                    Move::Tile(tile) => format!(
+                        "Move {}: tile {} is not next to the empty cell",
+                        index + 1,
+                        tile
+                    ),
+                };
+                illegal = Some((index + 1, err));
+                break;
+            }
+        }
+    }
+    let board = board.to_vec();
+    Replay {
+        applied,
+        reached_goal: illegal.is_none() && board == puzzle.goal,
+        board,
+        illegal,
+    }
+}
+
+#[test]
+fn solution_reaches_goal() {
+    let puzzle = Puzzle::new("puzzles/solv_3_1.map", "snail").unwrap();
+    let solution =
+        crate::ida_star::solve(&puzzle, &Default::default(), crate::heuristic::MANHATTAN).unwrap();
+    let text = npuzzle::format_blank_moves(&solution.moves);
+    let replay_of = |text: &str| replay(&puzzle, &parse_moves(text).unwrap());
+    let letters = replay_of(&text);
+    assert!(letters.reached_goal);
+    assert_eq!(letters.applied, 26);
+    let tiles = npuzzle::blank_to_tile_moves(puzzle.size, &puzzle.map, &solution.moves).unwrap();
+    let tiles = replay_of(&npuzzle::format_tile_moves(&tiles));
+    assert!(tiles.reached_goal);
+    assert_eq!(tiles.applied, 26);
+    assert_eq!(tiles.board, letters.board);
+}
+
+#[test]
+fn illegal_or_incomplete() {
+    // The empty cell is in the bottom left corner
+    let puzzle = Puzzle::new("puzzles/solv_3_1.map", "snail").unwrap();
+    let replay_of = |text: &str| replay(&puzzle, &parse_moves(text).unwrap());
+    let replay = replay_of("URL");
+    assert!(!replay.reached_goal);
+    assert_eq!(replay.applied, 3);
+    assert!(replay.illegal.is_none());
+    let replay = replay_of("UUUR");
+    assert_eq!(replay.applied, 2);
+    assert_eq!(replay.illegal.unwrap().0, 3);
+    assert!(!replay.reached_goal);
+    // The left move would wrap around to the end of the line above
+    assert_eq!(replay_of("L").illegal.unwrap().0, 1);
+    // Tile moves are reported like the letters, 4 is above the empty cell and 2 on its right
+    let replay = replay_of("4 4 2 5");
+    assert_eq!(replay.applied, 3);
+    assert_eq!(replay.illegal.unwrap().0, 4);
+    let replay = replay_of("4 4 5");
+    assert_eq!(replay.applied, 2);
+    assert_eq!(replay.illegal.unwrap().0, 3);
+    assert_eq!(replay_of("7").illegal.unwrap().0, 1);
+    assert!(parse_moves("U7").is_err());
+}
@@ -0,0 +1,176 @@
+use npuzzle::Board;
+use std::cell::RefCell;
+use std::collections::hash_map::Entry;
+use std::collections::{HashMap, VecDeque};
+use std::sync::{Arc, Mutex};
+
+// Tables for puzzles with more rows or columns don't fit in memory
+pub const MAX_SIZE: i32 = 4;
+
+// Every table built during this run, shared between threads
+static TABLES: Mutex<Vec<Arc<WalkingDistance>>> = Mutex::new(Vec::new());
+
+thread_local! {
+    // Tables used by the last evaluation of the thread, for rows and columns
+    static CURRENT: RefCell<Vec<Arc<WalkingDistance>>> = const { RefCell::new(Vec::new()) };
+}
+
+// Cost to move tiles between lines (rows or columns), ignoring their position in the line
+// -- a state is the amount of tiles in each line for each goal line and the line of the empty cell,
+// -- a move is a tile of a neighbor line going to the line of the empty cell
+pub struct WalkingDistance {
+    pub lines: usize,
+    // Amount of cells in each line, which is the amount of lines the other way
+    pub length: usize,
+    pub blank_line: usize,
+    pub costs: HashMap<u64, u8>,
+}
+
+impl WalkingDistance {
+    // Each count fits on 3 bits
+    fn key(counts: &[u8], blank_line: usize) -> u64 {
+        counts
+            .iter()
+            .fold(blank_line as u64, |key, &count| (key << 3) | count as u64)
+    }
+
+    fn decode(mut key: u64, counts: &mut [u8]) -> usize {
+        for count in counts.iter_mut().rev() {
+            *count = (key & 0b111) as u8;
+            key >>= 3;
+        }
+        key as usize
+    }
+
+    // Breadth-first search from the goal, where every tile is in its goal line
+    pub fn build(lines: usize, length: usize, blank_line: usize) -> WalkingDistance {
+        let mut counts = vec![0; lines * lines];
+        for line in 0..lines {
+            counts[line * lines + line] = if line == blank_line {
+                length as u8 - 1
+            } else {
+                length as u8
+            };
+        }
+
+        let mut costs = HashMap::new();
+        let mut queue = VecDeque::new();
+        let start = WalkingDistance::key(&counts, blank_line);
+        costs.insert(start, 0);
+        queue.push_back(start);
+        while let Some(key) = queue.pop_front() {
+            let cost = costs[&key];
+            let blank_line = WalkingDistance::decode(key, &mut counts);
+            let neighbor_lines = [blank_line.checked_sub(1), Some(blank_line + 1)];
+            for neighbor_line in neighbor_lines.into_iter().flatten() {
+                if neighbor_line >= lines {
+                    continue;
+                }
+                for goal_line in 0..lines {
+                    if counts[neighbor_line * lines + goal_line] == 0 {
+                        continue;
+                    }
+                    counts[neighbor_line * lines + goal_line] -= 1;
+                    counts[blank_line * lines + goal_line] += 1;
+                    let next = WalkingDistance::key(&counts, neighbor_line);
+                    if let Entry::Vacant(entry) = costs.entry(next) {
+                        entry.insert(cost + 1);
+                        queue.push_back(next);
+                    }
+                    counts[neighbor_line * lines + goal_line] += 1;
+                    counts[blank_line * lines + goal_line] -= 1;
+                }
+            }
+        }
+
+        WalkingDistance {
+            lines,
+            length,
+            blank_line,
+            costs,
+        }
+    }
+
+    pub fn cost(&self, counts: &[u8], blank_line: usize) -> u32 {
+        self.costs[&WalkingDistance::key(counts, blank_line)] as u32
+    }
+}
+
+// Find or build the table for the given lines and goal line of the empty cell
+pub fn get(lines: usize, length: usize, blank_line: usize) -> Arc<WalkingDistance> {
+    let matches = |table: &Arc<WalkingDistance>| {
+        table.lines == lines && table.length == length && table.blank_line == blank_line
+    };
+    let current = CURRENT.with(|current| current.borrow().iter().find(|t| matches(t)).cloned());
+    if let Some(table) = current {
+        return table;
+    }
+
+    let mut tables = TABLES.lock().unwrap();
+    let table = match tables.iter().find(|table| matches(table)) {
+        Some(table) => table.clone(),
+        None => {
+            let table = Arc::new(WalkingDistance::build(lines, length, blank_line));
+            tables.push(table.clone());
+            table
+        }
+    };
+    CURRENT.with(|current| {
+        let mut current = current.borrow_mut();
+        // Only rows and columns are needed at the same time
+        if current.len() >= 2 {
+            current.remove(0);
+        }
+        current.push(table.clone());
+    });
+    table
+}
+
+// Sum of the vertical and horizontal walking distances
+pub fn evaluate(size: i32, node: &Board, goal: &Board) -> u32 {
+    let columns = size as usize;
+    let rows = node.len() / columns;
+    let mut goal_positions = vec![0; node.len()];
+    for (index, cell) in goal.iter().enumerate() {
+        goal_positions[cell as usize] = index;
+    }
+
+    let mut row_counts = vec![0; rows * rows];
+    let mut column_counts = vec![0; columns * columns];
+    let (mut blank_row, mut blank_column) = (0, 0);
+    for (index, cell) in node.iter().enumerate() {
+        let (row, column) = (index / columns, index % columns);
+        if cell == 0 {
+            (blank_row, blank_column) = (row, column);
+        } else {
+            let goal = goal_positions[cell as usize];
+            row_counts[row * rows + goal / columns] += 1;
+            column_counts[column * columns + goal % columns] += 1;
+        }
+    }
+
+    let blank_goal = goal_positions[0];
+    get(rows, columns, blank_goal / columns).cost(&row_counts, blank_row)
+        + get(columns, rows, blank_goal % columns).cost(&column_counts, blank_column)
+}
+
+#[test]
+fn table_sizes() {
+    assert_eq!(WalkingDistance::build(3, 3, 2).costs.len(), 105);
+    assert_eq!(WalkingDistance::build(4, 4, 3).costs.len(), 24964);
+}
+
+#[test]
+fn goal_is_zero() {
+    let goal = Board::from(vec![1, 2, 3, 4, 12, 13, 14, 5, 11, 0, 15, 6, 10, 9, 8, 7]);
+    assert_eq!(evaluate(4, &goal, &goal), 0);
+}
+
+#[test]
+fn rectangle_single_move() {
+    // 3 rows of 4 columns
+    let goal = Board::from(vec![1, 2, 3, 4, 5, 6, 7, 8, 9, 10, 11, 0]);
+    let node = Board::from(vec![1, 2, 3, 4, 5, 6, 7, 0, 9, 10, 11, 8]);
+    assert_eq!(evaluate(4, &goal, &goal), 0);
+    assert_eq!(evaluate(4, &node, &goal), 1);
+}